@@ -0,0 +1,242 @@
+use std::path::Path;
+
+use crate::commands::{self, CommandResult};
+use crate::git::log::{self, LoggedCommit};
+use crate::git::CountSettings;
+use crate::{options, Options, RepoOptions};
+
+/// How many commits `diffding threshold` looks at unless told otherwise.
+const DEFAULT_COMMITS: usize = 200;
+
+/// Fewer commits than this say too little to adapt to.
+const MIN_COMMITS: usize = 10;
+
+/// The sizes of an author's recent commits, scored like the working tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitSizes {
+    pub author: String,
+    /// Smallest first
+    scores: Vec<f64>,
+}
+
+impl CommitSizes {
+    /// Scores each commit with the same excludes and weights as the
+    /// working tree.
+    pub fn new(author: String, commits: &[LoggedCommit], settings: &CountSettings) -> Self {
+        let mut scores: Vec<f64> = commits
+            .iter()
+            .map(|commit| {
+                let files = commit.counted_files(&settings.filter);
+                settings.weights.score_files(&files)
+            })
+            .collect();
+        scores.sort_by(f64::total_cmp);
+        Self { author, scores }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// The score `percentile` percent of commits are no bigger than, by
+    /// nearest rank. `None` without any commits.
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        let rank = (percentile / 100.0 * self.scores.len() as f64).ceil() as usize;
+        self.scores.get(rank.max(1) - 1).copied()
+    }
+
+    /// A threshold that most of the author's commits fit under: the 75th
+    /// percentile, rounded up to a multiple of ten.
+    pub fn suggested_threshold(&self) -> Option<i32> {
+        self.percentile(75.0).map(round_threshold)
+    }
+}
+
+/// `--write` sets the top-level threshold, which does nothing for a
+/// repository whose `repos` entry sets its own.
+fn check_writable(repo: Option<&RepoOptions>) -> CommandResult<()> {
+    match repo {
+        Some(repo) if repo.own_threshold => Err(format!(
+            "not written: the `repos` entry for {} sets threshold = {}, which overrides the \
+             top-level one; change it there instead",
+            repo.path.display(),
+            repo.threshold
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Rounds up to a multiple of ten, and never below ten.
+fn round_threshold(score: f64) -> i32 {
+    ((score / 10.0).ceil() as i32 * 10).max(10)
+}
+
+/// In adaptive mode the threshold follows a percentile of the author's
+/// recent commits instead of the configured one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSettings {
+    pub enabled: bool,
+    pub percentile: f64,
+    /// How many recent commits to take the percentile of
+    pub commits: usize,
+}
+
+impl AdaptiveSettings {
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            enabled: options.adaptive_threshold,
+            percentile: options.adaptive_percentile,
+            commits: options.adaptive_commits,
+        }
+    }
+
+    /// The threshold for the repository at `dir`, or `None` when adaptive
+    /// mode is off or there are too few commits to go by.
+    pub fn threshold(&self, dir: &Path, settings: &CountSettings) -> Option<i32> {
+        if !self.enabled {
+            return None;
+        }
+        let (author, commits) = log::author_commits(dir, self.commits).ok()?;
+        let sizes = CommitSizes::new(author, &commits, settings);
+        if sizes.len() < MIN_COMMITS {
+            return None;
+        }
+        sizes.percentile(self.percentile).map(round_threshold)
+    }
+}
+
+/// `diffding threshold [--commits <n>] [--write]`: prints how big the
+/// author's recent commits are and suggests a threshold, writing it to
+/// the config file with `--write`.
+pub fn command(options: &Options, args: &[String]) -> CommandResult<i32> {
+    let usage = "usage: diffding threshold [--commits <n>] [--write]";
+    let mut limit = DEFAULT_COMMITS;
+    let mut write = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--write" => write = true,
+            "--commits" => {
+                limit = args
+                    .next()
+                    .and_then(|commits| commits.parse().ok())
+                    .ok_or(usage)?
+            }
+            _ => return Err(usage.into()),
+        }
+    }
+
+    let (_, workdir) = commands::repository(options)?;
+    let (author, commits) = log::author_commits(&workdir, limit)?;
+    let sizes = CommitSizes::new(author, &commits, &CountSettings::from_options(options));
+    let Some(threshold) = sizes.suggested_threshold() else {
+        println!("No commits by {} to go by.", sizes.author);
+        return Ok(1);
+    };
+
+    println!("Last {} commits by {}:", sizes.len(), sizes.author);
+    for (label, percentile) in [("median", 50.0), ("p75", 75.0), ("p90", 90.0)] {
+        let score = sizes.percentile(percentile).unwrap_or_default();
+        println!("  {label:<8}{score:.0}");
+    }
+    println!(
+        "Suggested threshold: {threshold} (currently {})",
+        commands::configured_threshold(options, &workdir)
+    );
+    if sizes.len() < MIN_COMMITS {
+        println!("That's only a few commits, so take it with a grain of salt.");
+    }
+
+    if write {
+        check_writable(commands::repo_options(options, &workdir))?;
+        let path = options::write_setting("threshold", &threshold.to_string())?;
+        println!("Wrote threshold = {threshold} to {}", path.display());
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::filters::PathFilter;
+    use crate::git::{FileChange, FileStatus};
+
+    fn commit(files: Vec<FileChange>) -> LoggedCommit {
+        LoggedCommit {
+            commit: "c0ffee".to_string(),
+            time: 0,
+            subject: String::new(),
+            files,
+        }
+    }
+
+    fn sizes(lines: &[i32]) -> CommitSizes {
+        let commits: Vec<_> = lines
+            .iter()
+            .map(|lines| {
+                commit(vec![FileChange::new(
+                    "src/lib.rs",
+                    *lines,
+                    0,
+                    FileStatus::Modified,
+                )])
+            })
+            .collect();
+        CommitSizes::new(
+            "me@example.com".to_string(),
+            &commits,
+            &CountSettings::default(),
+        )
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let sizes = sizes(&[40, 10, 30, 20, 100, 60, 50, 90, 80, 70]);
+
+        assert_eq!(sizes.percentile(50.0), Some(50.0));
+        assert_eq!(sizes.percentile(75.0), Some(80.0));
+        assert_eq!(sizes.percentile(90.0), Some(90.0));
+        assert_eq!(sizes.percentile(0.0), Some(10.0));
+        assert_eq!(self::sizes(&[]).percentile(50.0), None);
+    }
+
+    #[test]
+    fn suggestion_rounds_up_the_75th_percentile() {
+        assert_eq!(sizes(&[12, 37, 41, 83]).suggested_threshold(), Some(50));
+        assert_eq!(sizes(&[1, 2, 3]).suggested_threshold(), Some(10));
+        assert_eq!(sizes(&[]).suggested_threshold(), None);
+    }
+
+    #[test]
+    fn writing_refuses_a_repo_with_its_own_threshold() {
+        let repo = |own_threshold| RepoOptions {
+            path: PathBuf::from("/src/app"),
+            threshold: 40,
+            own_threshold,
+        };
+
+        let error = check_writable(Some(&repo(true))).unwrap_err().to_string();
+        assert!(error.contains("sets threshold = 40"));
+        assert!(check_writable(Some(&repo(false))).is_ok());
+        assert!(check_writable(None).is_ok());
+    }
+
+    #[test]
+    fn excluded_files_are_left_out_of_commit_sizes() {
+        let settings = CountSettings {
+            filter: PathFilter::new(&[], &["Cargo.lock".to_string()], false),
+            ..CountSettings::default()
+        };
+        let commits = [commit(vec![
+            FileChange::new("src/lib.rs", 20, 5, FileStatus::Modified),
+            FileChange::new("Cargo.lock", 400, 300, FileStatus::Modified),
+        ])];
+
+        let sizes = CommitSizes::new("me@example.com".to_string(), &commits, &settings);
+
+        assert_eq!(sizes.percentile(50.0), Some(25.0));
+    }
+}
@@ -0,0 +1,343 @@
+use std::str::FromStr;
+
+use crate::commands::{self, CommandResult};
+use crate::git::log::{self, LoggedCommit};
+use crate::git::{CountSettings, DiffStat, Vcs};
+use crate::Options;
+
+/// How `diffding audit` prints its report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+    /// JUnit XML, one test case per commit, for CI test reports
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "junit" => Ok(ReportFormat::Junit),
+            _ => Err(format!(
+                "unknown format: {s} (expected text, json or junit)"
+            )),
+        }
+    }
+}
+
+/// One commit, scored like the working tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitAudit {
+    pub commit: String,
+    pub subject: String,
+    /// Lines in excluded files are left out
+    pub changes: DiffStat,
+    pub score: f64,
+}
+
+impl CommitAudit {
+    pub fn new(commit: &LoggedCommit, settings: &CountSettings) -> Self {
+        let files = commit.counted_files(&settings.filter);
+        Self {
+            commit: commit.commit.clone(),
+            subject: commit.subject.clone(),
+            changes: DiffStat::from_files(&files),
+            score: settings.weights.score_files(&files),
+        }
+    }
+
+    fn short_commit(&self) -> &str {
+        self.commit.get(..7).unwrap_or(&self.commit)
+    }
+}
+
+/// The commits in a range, checked against the limit each is held to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audit {
+    pub range: String,
+    pub max: i32,
+    /// Oldest first
+    pub commits: Vec<CommitAudit>,
+}
+
+impl Audit {
+    pub fn is_over(&self, commit: &CommitAudit) -> bool {
+        commit.score > f64::from(self.max)
+    }
+
+    pub fn violations(&self) -> usize {
+        self.commits
+            .iter()
+            .filter(|commit| self.is_over(commit))
+            .count()
+    }
+
+    pub fn report(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.text(),
+            ReportFormat::Json => self.json(),
+            ReportFormat::Junit => self.junit(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for commit in &self.commits {
+            let flag = if self.is_over(commit) { "OVER" } else { "ok" };
+            let counts = format!(
+                "+{} -{}",
+                commit.changes.insertions, commit.changes.deletions
+            );
+            text += &format!(
+                "{:<4} {} {:<14} {:>6.0}  {}\n",
+                flag,
+                commit.short_commit(),
+                counts,
+                commit.score,
+                commit.subject
+            );
+        }
+        text += &format!(
+            "{} of {} commits in {} over the limit of {}\n",
+            self.violations(),
+            self.commits.len(),
+            self.range,
+            self.max
+        );
+        text
+    }
+
+    fn json(&self) -> String {
+        let commits: Vec<String> = self
+            .commits
+            .iter()
+            .map(|commit| {
+                format!(
+                    "    {{\"commit\": {}, \"subject\": {}, \"insertions\": {}, \"deletions\": {}, \
+                     \"score\": {}, \"over\": {}}}",
+                    json_string(&commit.commit),
+                    json_string(&commit.subject),
+                    commit.changes.insertions,
+                    commit.changes.deletions,
+                    commit.score,
+                    self.is_over(commit)
+                )
+            })
+            .collect();
+        let commits = if commits.is_empty() {
+            "[]".to_string()
+        } else {
+            format!("[\n{}\n  ]", commits.join(",\n"))
+        };
+        format!(
+            "{{\n  \"range\": {},\n  \"max\": {},\n  \"violations\": {},\n  \"commits\": {}\n}}\n",
+            json_string(&self.range),
+            self.max,
+            self.violations(),
+            commits
+        )
+    }
+
+    fn junit(&self) -> String {
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuite name=\"diffding audit {}\" tests=\"{}\" failures=\"{}\">\n",
+            xml_escape(&self.range),
+            self.commits.len(),
+            self.violations()
+        );
+        for commit in &self.commits {
+            xml += &format!(
+                "  <testcase classname=\"diffding.audit\" name=\"{} {}\"",
+                commit.short_commit(),
+                xml_escape(&commit.subject)
+            );
+            if self.is_over(commit) {
+                xml += &format!(
+                    ">\n    <failure message=\"score {:.0} is over the limit of {}\">\
+                     +{} -{}</failure>\n  </testcase>\n",
+                    commit.score, self.max, commit.changes.insertions, commit.changes.deletions
+                );
+            } else {
+                xml += "/>\n";
+            }
+        }
+        xml + "</testsuite>\n"
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\t' => json += "\\t",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json + "\""
+}
+
+fn xml_escape(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_control() || *c == '\t')
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// `diffding audit <range> [--max <n>] [--format text|json|junit]`: checks
+/// each commit in `range` against the limit, the same threshold `check`
+/// uses unless `--max` is given, and fails when any is over it.
+pub fn command(options: &Options, args: &[String]) -> CommandResult<i32> {
+    let usage = "usage: diffding audit <range> [--max <n>] [--format text|json|junit]";
+    let mut range = None;
+    let mut max = None;
+    let mut format = ReportFormat::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max" => max = Some(args.next().ok_or(usage)?.parse::<i32>()?),
+            "--format" => format = args.next().ok_or(usage)?.parse()?,
+            _ if range.is_none() && !arg.starts_with('-') => range = Some(arg.clone()),
+            _ => return Err(usage.into()),
+        }
+    }
+    let range = range.ok_or(usage)?;
+
+    let (backend, workdir) = commands::repository(options)?;
+    if backend.vcs() != Vcs::Git {
+        return Err("audit only works in git repositories".into());
+    }
+    let settings = CountSettings::from_options(options);
+    let max = max.unwrap_or_else(|| commands::threshold(options, &workdir, &settings));
+
+    let commits = log::range_commits(&workdir, &range)?;
+    let audit = Audit {
+        range,
+        max,
+        commits: commits
+            .iter()
+            .map(|commit| CommitAudit::new(commit, &settings))
+            .collect(),
+    };
+    print!("{}", audit.report(format));
+    Ok(if audit.violations() > 0 { 1 } else { 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::PathFilter;
+    use crate::git::{FileChange, FileStatus};
+
+    fn commit(commit: &str, subject: &str, files: Vec<FileChange>) -> LoggedCommit {
+        LoggedCommit {
+            commit: commit.to_string(),
+            time: 0,
+            subject: subject.to_string(),
+            files,
+        }
+    }
+
+    fn audit() -> Audit {
+        let settings = CountSettings {
+            filter: PathFilter::new(&[], &["Cargo.lock".to_string()], false),
+            ..CountSettings::default()
+        };
+        let commits = [
+            commit(
+                "1111111aaaa",
+                "Add the \"audit\" command",
+                vec![
+                    FileChange::new("src/audit.rs", 150, 20, FileStatus::Added),
+                    FileChange::new("Cargo.lock", 900, 0, FileStatus::Modified),
+                ],
+            ),
+            commit(
+                "2222222bbbb",
+                "Fix <range> parsing",
+                vec![FileChange::new("src/audit.rs", 4, 1, FileStatus::Modified)],
+            ),
+        ];
+        Audit {
+            range: "main..HEAD".to_string(),
+            max: 100,
+            commits: commits
+                .iter()
+                .map(|commit| CommitAudit::new(commit, &settings))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn commits_are_scored_without_excluded_files() {
+        let audit = audit();
+
+        assert_eq!(audit.commits[0].changes, DiffStat::new(150, 20));
+        assert_eq!(audit.commits[0].score, 170.0);
+        assert_eq!(audit.violations(), 1);
+    }
+
+    #[test]
+    fn text_report_flags_commits_over_the_limit() {
+        assert_eq!(
+            audit().report(ReportFormat::Text),
+            "OVER 1111111 +150 -20          170  Add the \"audit\" command\n\
+             ok   2222222 +4 -1               5  Fix <range> parsing\n\
+             1 of 2 commits in main..HEAD over the limit of 100\n"
+        );
+    }
+
+    #[test]
+    fn json_report_escapes_subjects() {
+        let json = audit().report(ReportFormat::Json);
+
+        assert!(json.contains("\"violations\": 1,"));
+        assert!(json.contains(
+            "{\"commit\": \"1111111aaaa\", \"subject\": \"Add the \\\"audit\\\" command\", \
+             \"insertions\": 150, \"deletions\": 20, \"score\": 170, \"over\": true}"
+        ));
+        assert_eq!(json_string("tab\there\u{1}"), "\"tab\\there\\u0001\"");
+    }
+
+    #[test]
+    fn junit_report_fails_commits_over_the_limit() {
+        assert_eq!(
+            audit().report(ReportFormat::Junit),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuite name=\"diffding audit main..HEAD\" tests=\"2\" failures=\"1\">\n\
+             \x20 <testcase classname=\"diffding.audit\" name=\"1111111 Add the &quot;audit&quot; command\">\n\
+             \x20   <failure message=\"score 170 is over the limit of 100\">+150 -20</failure>\n\
+             \x20 </testcase>\n\
+             \x20 <testcase classname=\"diffding.audit\" name=\"2222222 Fix &lt;range&gt; parsing\"/>\n\
+             </testsuite>\n"
+        );
+    }
+
+    #[test]
+    fn empty_range() {
+        let audit = Audit {
+            range: "main..main".to_string(),
+            max: 100,
+            commits: Vec::new(),
+        };
+
+        assert!(audit.report(ReportFormat::Json).contains("\"commits\": []"));
+        assert_eq!(audit.violations(), 0);
+    }
+}
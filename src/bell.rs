@@ -53,12 +53,7 @@ pub fn ring(sound_path: &Option<PathBuf>) {
             load_default_sound(&mut wav);
         }
         Some(path) => {
-            if path.exists() {
-                let result = wav.load(path);
-                if let Err(_) = result {
-                    load_default_sound(&mut wav);
-                }
-            } else {
+            if !path.exists() || wav.load(path).is_err() {
                 load_default_sound(&mut wav);
             }
         }
@@ -0,0 +1,161 @@
+use crate::commands::{self, CommandResult};
+use crate::git::hooks::{self, Installed};
+use crate::git::{staged_changes, CountSettings, DiffStat, FileChange, Vcs};
+use crate::reminders::format_bytes;
+use crate::scoring::Weights;
+use crate::Options;
+
+/// The staged changes, scored against the limit a commit is held to.
+#[derive(Debug, Clone)]
+pub struct StagedCheck {
+    pub files: Vec<FileChange>,
+    /// Lines in skipped files are left out
+    pub changes: DiffStat,
+    pub score: f64,
+    pub max: i32,
+}
+
+impl StagedCheck {
+    pub fn new(mut files: Vec<FileChange>, weights: &Weights, max: i32) -> Self {
+        // Counted files first, biggest first, like the file list
+        files.sort_by(|a, b| {
+            a.skipped
+                .is_some()
+                .cmp(&b.skipped.is_some())
+                .then(b.churn().cmp(&a.churn()))
+                .then(a.path.cmp(&b.path))
+        });
+        Self {
+            changes: DiffStat::from_files(&files),
+            score: weights.score_files(&files),
+            files,
+            max,
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.score > f64::from(self.max)
+    }
+
+    /// One line per file, then the total.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for file in &self.files {
+            let counts = match file.skipped {
+                Some(reason) => format!("skipped ({})", reason.label()),
+                None if file.binary => {
+                    format!("bin {}", file.bytes.map(format_bytes).unwrap_or_default())
+                }
+                None => format!("+{} -{}", file.insertions, file.deletions),
+            };
+            report += &format!(
+                "  {} {:<16} {}\n",
+                file.status.code(),
+                counts,
+                file.path.display()
+            );
+        }
+        report += &format!(
+            "Staged: +{} -{}, scoring {:.0} of {}\n",
+            self.changes.insertions, self.changes.deletions, self.score, self.max
+        );
+        report
+    }
+}
+
+/// `diffding check [--max <n>]`: fails when the staged changes score more
+/// than `--max`, or the threshold when it isn't given. Meant for a
+/// pre-commit hook.
+pub fn command(options: &Options, args: &[String]) -> CommandResult<i32> {
+    let max = match args {
+        [] => None,
+        [flag, max] if flag == "--max" => Some(max.parse::<i32>()?),
+        _ => return Err("usage: diffding check [--max <n>]".into()),
+    };
+
+    let (backend, workdir) = commands::cli_repository(options)?;
+    if backend.vcs() != Vcs::Git {
+        return Err("only git has staged changes to check".into());
+    }
+    let settings = CountSettings::from_options(options);
+    let max = max.unwrap_or_else(|| commands::threshold(options, &workdir, &settings));
+
+    let files = staged_changes(backend.as_ref(), &settings)?;
+    let check = StagedCheck::new(files, &settings.weights, max);
+    print!("{}", check.report());
+    if check.is_over() {
+        eprintln!(
+            "The staged changes are over the limit of {max}. Split them into smaller commits, \
+             or commit with --no-verify to skip the check."
+        );
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// `diffding hooks install [--max <n>]`: runs `diffding check` before each
+/// commit.
+pub fn hooks_command(options: &Options, args: &[String]) -> CommandResult<i32> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let max = match args.as_slice() {
+        ["install"] => None,
+        ["install", "--max", max] => Some(max.parse::<i32>()?),
+        _ => return Err("usage: diffding hooks install [--max <n>]".into()),
+    };
+
+    let (backend, workdir) = commands::repository(options)?;
+    if backend.vcs() != Vcs::Git {
+        return Err("hooks can only be installed in git repositories".into());
+    }
+    let hooks_dir = hooks::hooks_dir(&workdir)?;
+    let hook = hooks_dir.join("pre-commit");
+    match hooks::install(&hooks_dir, max)? {
+        Installed::New => println!("Installed {}", hook.display()),
+        Installed::Updated => println!("Updated {}", hook.display()),
+        Installed::Chained => println!(
+            "Installed {}; the hook that was there is now pre-commit.local and runs first",
+            hook.display()
+        ),
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::SkipReason;
+    use crate::git::FileStatus;
+
+    fn files() -> Vec<FileChange> {
+        let mut lock = FileChange::new("Cargo.lock", 300, 200, FileStatus::Modified);
+        lock.skipped = Some(SkipReason::Excluded);
+        vec![
+            lock,
+            FileChange::new("src/lib.rs", 12, 3, FileStatus::Modified),
+            FileChange::new("src/check.rs", 40, 0, FileStatus::Added),
+        ]
+    }
+
+    #[test]
+    fn skipped_files_dont_count_against_the_limit() {
+        let check = StagedCheck::new(files(), &Weights::default(), 60);
+
+        assert_eq!(check.changes, DiffStat::new(52, 3));
+        assert_eq!(check.score, 55.0);
+        assert!(!check.is_over());
+        assert!(StagedCheck::new(files(), &Weights::default(), 50).is_over());
+    }
+
+    #[test]
+    fn report_lists_each_file_then_the_total() {
+        let check = StagedCheck::new(files(), &Weights::default(), 60);
+
+        assert_eq!(
+            check.report(),
+            "  A +40 -0           src/check.rs\n\
+             \x20 M +12 -3           src/lib.rs\n\
+             \x20 M skipped (excluded) Cargo.lock\n\
+             Staged: +52 -3, scoring 55 of 60\n"
+        );
+    }
+}
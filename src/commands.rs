@@ -0,0 +1,88 @@
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::adaptive::AdaptiveSettings;
+use crate::git::{open_backend, BackendKind, CountSettings, GitBackend};
+use crate::{adaptive, audit, check, snapshots, Options, RepoOptions};
+
+/// Subcommands share the git backends' error type.
+pub type CommandResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Subcommands run once and exit instead of starting the TUI.
+const SUBCOMMANDS: &[&str] = &["audit", "check", "hooks", "snapshots", "threshold"];
+
+pub fn is_subcommand(name: &str) -> bool {
+    SUBCOMMANDS.contains(&name)
+}
+
+/// Runs `options.subcommand` and returns the exit code.
+pub fn run(options: &Options) -> i32 {
+    let Some((name, args)) = options.subcommand.split_first() else {
+        return 0;
+    };
+    let result = match name.as_str() {
+        "audit" => audit::command(options, args),
+        "check" => check::command(options, args),
+        "hooks" => check::hooks_command(options, args),
+        "snapshots" => snapshots::command(options, args),
+        "threshold" => adaptive::command(options, args),
+        _ => Err(format!("unknown command: {name}").into()),
+    };
+
+    result.unwrap_or_else(|error| {
+        eprintln!("diffding {name}: {error}");
+        1
+    })
+}
+
+/// The repository a subcommand works on: `--repo` when given, otherwise
+/// the one the current directory is in, like git.
+pub fn repository(options: &Options) -> CommandResult<(Box<dyn GitBackend>, PathBuf)> {
+    open_repository(options, options.backend)
+}
+
+/// Like `repository`, but always through the git command, which honours
+/// the `GIT_INDEX_FILE` git sets for hooks during `git commit -a`.
+pub fn cli_repository(options: &Options) -> CommandResult<(Box<dyn GitBackend>, PathBuf)> {
+    open_repository(options, BackendKind::Cli)
+}
+
+fn open_repository(
+    options: &Options,
+    kind: BackendKind,
+) -> CommandResult<(Box<dyn GitBackend>, PathBuf)> {
+    let path = match &options.repo {
+        Some(path) => path.clone(),
+        None => env::current_dir()?,
+    };
+    let backend = open_backend(kind, &path)?;
+    let workdir = backend.workdir()?;
+    Ok((backend, workdir))
+}
+
+/// The threshold the repository at `workdir` is held to: the adaptive one
+/// when that's on and there's enough history, otherwise the configured one.
+pub fn threshold(options: &Options, workdir: &Path, settings: &CountSettings) -> i32 {
+    AdaptiveSettings::from_options(options)
+        .threshold(workdir, settings)
+        .unwrap_or_else(|| configured_threshold(options, workdir))
+}
+
+/// The threshold of the `repos` entry for the repository at `workdir`, or
+/// the top-level one when it isn't listed.
+pub fn configured_threshold(options: &Options, workdir: &Path) -> i32 {
+    repo_options(options, workdir).map_or(options.threshold, |repo| repo.threshold)
+}
+
+/// The `repos` entry for the repository at `workdir`, if it's listed.
+pub fn repo_options<'a>(options: &'a Options, workdir: &Path) -> Option<&'a RepoOptions> {
+    let workdir = workdir.canonicalize().ok()?;
+    options.repos.iter().find(|repo| {
+        open_backend(options.backend, &repo.path)
+            .and_then(|backend| backend.workdir())
+            .ok()
+            .and_then(|repo_workdir| repo_workdir.canonicalize().ok())
+            .is_some_and(|repo_workdir| repo_workdir == workdir)
+    })
+}
@@ -0,0 +1,360 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans, Text};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap};
+use tui::Frame;
+
+use crate::git::FileChange;
+use crate::GitState;
+
+/// What a commit from the dialog includes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommitScope {
+    /// Only what's already staged
+    #[default]
+    Staged,
+    /// Every change, untracked files included (`git add --all` first)
+    All,
+}
+
+impl CommitScope {
+    fn label(self) -> &'static str {
+        match self {
+            CommitScope::Staged => "staged changes",
+            CommitScope::All => "all changes",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DialogStatus {
+    #[default]
+    Editing,
+    /// Waiting for `git commit`
+    Committing,
+    /// The commit was refused; holds what git and the hooks printed
+    Failed(String),
+}
+
+/// What the manager should do after a key press in the dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogAction {
+    None,
+    Commit,
+    Close,
+}
+
+/// The commit dialog: a small multi-line editor for the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitDialog {
+    lines: Vec<String>,
+    /// Line and character the cursor is on
+    cursor: (usize, usize),
+    pub scope: CommitScope,
+    pub status: DialogStatus,
+}
+
+impl Default for CommitDialog {
+    fn default() -> Self {
+        Self {
+            lines: vec![String::new()],
+            cursor: (0, 0),
+            scope: CommitScope::default(),
+            status: DialogStatus::default(),
+        }
+    }
+}
+
+impl CommitDialog {
+    /// Starts on everything when nothing is staged yet.
+    pub fn new(git_state: &GitState) -> Self {
+        let scope = if git_state.changed_files.staged.is_empty() {
+            CommitScope::All
+        } else {
+            CommitScope::Staged
+        };
+        Self {
+            scope,
+            ..Self::default()
+        }
+    }
+
+    /// The message as typed, without surrounding blank lines.
+    pub fn message(&self) -> String {
+        self.lines.join("\n").trim().to_string()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> DialogAction {
+        if self.status == DialogStatus::Committing {
+            return DialogAction::None;
+        }
+        let (row, col) = self.cursor;
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => return DialogAction::Close,
+            KeyCode::Char('s') if control => {
+                if self.message().is_empty() {
+                    self.status = DialogStatus::Failed("The commit message is empty.".to_string());
+                    return DialogAction::None;
+                }
+                self.status = DialogStatus::Committing;
+                return DialogAction::Commit;
+            }
+            KeyCode::Tab => {
+                self.scope = match self.scope {
+                    CommitScope::Staged => CommitScope::All,
+                    CommitScope::All => CommitScope::Staged,
+                };
+            }
+            KeyCode::Enter => {
+                let byte = byte_index(&self.lines[row], col);
+                let rest = self.lines[row].split_off(byte);
+                self.lines.insert(row + 1, rest);
+                self.cursor = (row + 1, 0);
+            }
+            KeyCode::Backspace if col > 0 => {
+                let byte = byte_index(&self.lines[row], col - 1);
+                self.lines[row].remove(byte);
+                self.cursor = (row, col - 1);
+            }
+            KeyCode::Backspace if row > 0 => {
+                let line = self.lines.remove(row);
+                let previous_len = self.lines[row - 1].chars().count();
+                self.lines[row - 1].push_str(&line);
+                self.cursor = (row - 1, previous_len);
+            }
+            KeyCode::Left if col > 0 => self.cursor = (row, col - 1),
+            KeyCode::Right if col < self.line_len(row) => self.cursor = (row, col + 1),
+            KeyCode::Up if row > 0 => self.cursor = (row - 1, col.min(self.line_len(row - 1))),
+            KeyCode::Down if row + 1 < self.lines.len() => {
+                self.cursor = (row + 1, col.min(self.line_len(row + 1)))
+            }
+            KeyCode::Home => self.cursor = (row, 0),
+            KeyCode::End => self.cursor = (row, self.line_len(row)),
+            KeyCode::Char(c) if !control => {
+                let byte = byte_index(&self.lines[row], col);
+                self.lines[row].insert(byte, c);
+                self.cursor = (row, col + 1);
+            }
+            _ => {}
+        }
+        DialogAction::None
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+}
+
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices()
+        .nth(col)
+        .map_or(line.len(), |(index, _)| index)
+}
+
+/// The files a commit in `scope` would include.
+fn files_in_scope(git_state: &GitState, scope: CommitScope) -> Vec<&FileChange> {
+    let files = &git_state.changed_files;
+    match scope {
+        CommitScope::Staged => files.staged.iter().collect(),
+        CommitScope::All => files.combined.iter().chain(&files.untracked).collect(),
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, area: Rect, dialog: &CommitDialog, git_state: &GitState) {
+    let area = centered(area, 80, 80);
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Commit ")
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let output_height = match &dialog.status {
+        DialogStatus::Failed(output) => (output.lines().count() as u16 + 2).min(10),
+        _ => 0,
+    };
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .horizontal_margin(1)
+        .constraints(
+            [
+                Constraint::Length(1),
+                Constraint::Length(8),
+                Constraint::Min(3),
+                Constraint::Length(output_height),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(inner);
+
+    let files = files_in_scope(git_state, dialog.scope);
+    let scope = Spans::from(vec![
+        Span::raw("Committing "),
+        Span::styled(
+            dialog.scope.label(),
+            Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!(" ({} files)", files.len())),
+    ]);
+    f.render_widget(Paragraph::new(scope), split[0]);
+
+    draw_message(f, split[1], dialog);
+    draw_files(f, split[2], &files);
+
+    if let DialogStatus::Failed(output) = &dialog.status {
+        let output_box = Paragraph::new(Text::from(output.as_str()))
+            .style(Style::default().fg(Color::LightRed))
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Commit failed "),
+            );
+        f.render_widget(output_box, split[3]);
+    }
+
+    let help = match dialog.status {
+        DialogStatus::Committing => "Committing...",
+        _ => "<ctrl-s> commit / <tab> staged or all / <esc> cancel",
+    };
+    f.render_widget(
+        Paragraph::new(help).style(Style::default().fg(Color::LightYellow)),
+        split[4],
+    );
+}
+
+fn draw_message<B: Backend>(f: &mut Frame<B>, area: Rect, dialog: &CommitDialog) {
+    let block = Block::default().borders(Borders::ALL).title(" Message ");
+    let inner = block.inner(area);
+    let (row, col) = dialog.cursor;
+    // Keeps the cursor's line in view
+    let scroll = (row + 1).saturating_sub(inner.height as usize) as u16;
+
+    let text: Vec<Spans> = dialog
+        .lines
+        .iter()
+        .map(|line| Spans::from(line.as_str()))
+        .collect();
+    f.render_widget(Paragraph::new(text).block(block).scroll((scroll, 0)), area);
+
+    if dialog.status != DialogStatus::Committing {
+        let x = inner.x + (col as u16).min(inner.width.saturating_sub(1));
+        f.set_cursor(x, inner.y + row as u16 - scroll);
+    }
+}
+
+fn draw_files<B: Backend>(f: &mut Frame<B>, area: Rect, files: &[&FileChange]) {
+    let items: Vec<ListItem> = files
+        .iter()
+        .map(|file| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{} ", file.status.code()),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::raw(file.path.display().to_string()),
+                Span::styled(
+                    format!(" +{}", file.insertions),
+                    Style::default().fg(Color::LightGreen),
+                ),
+                Span::styled(
+                    format!(" -{}", file.deletions),
+                    Style::default().fg(Color::LightRed),
+                ),
+            ]))
+        })
+        .collect();
+    let title = if files.is_empty() {
+        " Nothing to commit "
+    } else {
+        " Files "
+    };
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
+}
+
+/// A rectangle `percent_x` by `percent_y` of `area`, centred in it.
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = area.height * percent_y / 100;
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(dialog: &mut CommitDialog, keys: &[KeyCode]) {
+        for key in keys {
+            dialog.handle_key(KeyEvent::new(*key, KeyModifiers::NONE));
+        }
+    }
+
+    fn type_text(dialog: &mut CommitDialog, text: &str) {
+        let keys: Vec<KeyCode> = text
+            .chars()
+            .map(|c| match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            })
+            .collect();
+        type_keys(dialog, &keys);
+    }
+
+    fn ctrl_s() -> KeyEvent {
+        KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn edits_a_multi_line_message() {
+        let mut dialog = CommitDialog::default();
+        type_text(&mut dialog, "Fix teh bug\n\nDetails");
+        type_keys(&mut dialog, &[KeyCode::Up, KeyCode::Up, KeyCode::End]);
+        type_keys(&mut dialog, &[KeyCode::Left; 4]);
+        type_keys(&mut dialog, &[KeyCode::Backspace, KeyCode::Backspace]);
+        type_text(&mut dialog, "he");
+
+        assert_eq!(dialog.message(), "Fix the bug\n\nDetails");
+
+        type_keys(&mut dialog, &[KeyCode::Down, KeyCode::Down, KeyCode::Home]);
+        type_keys(&mut dialog, &[KeyCode::Backspace, KeyCode::Backspace]);
+        assert_eq!(dialog.message(), "Fix the bugDetails");
+    }
+
+    #[test]
+    fn refuses_to_commit_an_empty_message() {
+        let mut dialog = CommitDialog::default();
+        type_text(&mut dialog, " \n");
+
+        assert_eq!(dialog.handle_key(ctrl_s()), DialogAction::None);
+        assert!(matches!(dialog.status, DialogStatus::Failed(_)));
+
+        type_text(&mut dialog, "Add the dialog");
+        assert_eq!(dialog.handle_key(ctrl_s()), DialogAction::Commit);
+        assert_eq!(dialog.status, DialogStatus::Committing);
+        // Keys are ignored until the commit finishes
+        type_text(&mut dialog, "x");
+        assert_eq!(dialog.message(), "Add the dialog");
+    }
+
+    #[test]
+    fn tab_switches_between_staged_and_all() {
+        let mut dialog = CommitDialog::default();
+        assert_eq!(dialog.scope, CommitScope::Staged);
+        type_keys(&mut dialog, &[KeyCode::Tab]);
+        assert_eq!(dialog.scope, CommitScope::All);
+    }
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use crossterm::event::{Event, EventStream, KeyCode};
+use crossterm::event::{Event, EventStream, KeyCode, KeyModifiers};
 use futures_timer::Delay;
 use tokio::select;
 use tokio::sync::mpsc::Sender;
@@ -9,7 +9,20 @@ use tokio::sync::mpsc::Sender;
 use crate::manager::ManagerMessage;
 use crate::{FutureExt, Options, StreamExt};
 
-pub async fn keyboard_events(tx: Sender<ManagerMessage>, options: Arc<Options>) {
+/// How often to redraw so times like "last commit 5 min ago" and the snooze
+/// countdown stay current.
+const REDRAW_INTERVAL: Duration = Duration::from_secs(30);
+
+pub async fn redraw_ticks(tx: Sender<ManagerMessage>) {
+    loop {
+        Delay::new(REDRAW_INTERVAL).await;
+        if tx.send(ManagerMessage::Redraw).await.is_err() {
+            break;
+        }
+    }
+}
+
+pub async fn keyboard_events(tx: Sender<ManagerMessage>, _options: Arc<Options>) {
     let mut reader = EventStream::new();
 
     loop {
@@ -24,24 +37,12 @@ pub async fn keyboard_events(tx: Sender<ManagerMessage>, options: Arc<Options>)
                             tx.send(ManagerMessage::Redraw).await.unwrap();
                         }
                         if let Event::Key(key_event) = event {
-                            match key_event.code {
-                                KeyCode::Char('q') => {
-                                    tx.send(ManagerMessage::Quit).await.unwrap();
-                                },
-                                KeyCode::Char('c') => {
-                                    if key_event.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) {
-                                        tx.send(ManagerMessage::Quit).await.unwrap();
-                                    }
-                                },
-                                KeyCode::Char(' ') => {
-                                    tx.send(ManagerMessage::Snooze).await.unwrap();
-                                },
-                                KeyCode::Char('b') => {
-                                    tx.send(ManagerMessage::Bell).await.unwrap();
-                                },
-
-                                _ => {}
-                            }
+                            let message = if key_event.code == KeyCode::Char('c') && key_event.modifiers.contains(KeyModifiers::CONTROL) {
+                                ManagerMessage::Quit
+                            } else {
+                                ManagerMessage::Key(key_event)
+                            };
+                            tx.send(message).await.unwrap();
                         }
 
                     }
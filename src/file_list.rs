@@ -0,0 +1,173 @@
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Cell, List, ListItem, Row, Table};
+use tui::Frame;
+
+use crate::filters::SkipReason;
+use crate::git::FileChange;
+use crate::reminders::format_bytes;
+use crate::GitState;
+
+const BAR_WIDTH: i32 = 20;
+
+pub fn file_list<B: Backend>(f: &mut Frame<B>, area: Rect, git_state: &GitState) {
+    let area = if git_state.conflicts.is_empty() {
+        area
+    } else {
+        // Conflicts first, with room for the title and up to five files
+        let height = git_state.conflicts.len().min(5) as u16 + 2;
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(height), Constraint::Min(0)].as_ref())
+            .split(area);
+        conflict_list(f, split[0], git_state);
+        split[1]
+    };
+
+    let mut files: Vec<&FileChange> = git_state.files.iter().collect();
+    // Counted files first, then skipped ones, each sorted by churn
+    files.sort_by(|a, b| {
+        a.skipped
+            .is_some()
+            .cmp(&b.skipped.is_some())
+            .then(b.churn().cmp(&a.churn()))
+            .then(a.path.cmp(&b.path))
+    });
+
+    // Bars are scaled to the counted files, so a big skipped lockfile
+    // doesn't shrink the rest
+    let max_churn = files
+        .iter()
+        .filter(|file| file.skipped.is_none())
+        .map(|file| file.churn())
+        .max()
+        .unwrap_or(0);
+
+    let rows = files.iter().map(|file| {
+        if let Some(reason) = file.skipped {
+            // Files too large to add are flagged rather than greyed out
+            let (size, style) = match file.bytes {
+                Some(bytes) if reason == SkipReason::TooLarge => {
+                    (format_bytes(bytes), Style::default().fg(Color::LightRed))
+                }
+                _ => (
+                    file.churn().to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            };
+            return Row::new(vec![
+                Cell::from(file.status.code().to_string()),
+                Cell::from(file.path.display().to_string()),
+                Cell::from(format!("{size} {}", reason.label())),
+                Cell::from(""),
+            ])
+            .style(style);
+        }
+
+        let counts = if file.binary {
+            let size = file.bytes.map(format_bytes).unwrap_or_default();
+            Spans::from(Span::styled(
+                format!("bin {size}"),
+                Style::default().fg(Color::Gray),
+            ))
+        } else {
+            Spans::from(vec![
+                Span::styled(
+                    format!("+{}", file.insertions),
+                    Style::default().fg(Color::LightGreen),
+                ),
+                Span::raw(" "),
+                Span::styled(
+                    format!("-{}", file.deletions),
+                    Style::default().fg(Color::LightRed),
+                ),
+            ])
+        };
+
+        Row::new(vec![
+            Cell::from(file.status.code().to_string()),
+            Cell::from(path_spans(file)),
+            Cell::from(counts),
+            Cell::from(churn_bar(file, max_churn)),
+        ])
+    });
+
+    let title = format!("FILES ({})", files.len());
+    let table = Table::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::NONE)
+                .title(title)
+                .style(Style::default().bg(Color::Black).fg(Color::White)),
+        )
+        .widths(&[
+            Constraint::Length(1),
+            Constraint::Min(20),
+            Constraint::Length(18),
+            Constraint::Length(BAR_WIDTH as u16),
+        ]);
+
+    f.render_widget(table, area);
+}
+
+/// Files with unresolved conflict markers, listed apart from the counts.
+fn conflict_list<B: Backend>(f: &mut Frame<B>, area: Rect, git_state: &GitState) {
+    let items: Vec<ListItem> = git_state
+        .conflicts
+        .iter()
+        .map(|path| ListItem::new(path.display().to_string()))
+        .collect();
+
+    let title = format!("CONFLICT MARKERS ({})", git_state.conflicts.len());
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::NONE)
+            .title(title)
+            .style(Style::default().bg(Color::Black).fg(Color::LightRed)),
+    );
+
+    f.render_widget(list, area);
+}
+
+/// The path, with the submodule it belongs to highlighted.
+fn path_spans<'a>(file: &FileChange) -> Spans<'a> {
+    match &file.submodule {
+        Some(submodule) => Spans::from(vec![
+            Span::styled(
+                format!("{}/", submodule.display()),
+                Style::default().fg(Color::Cyan),
+            ),
+            Span::raw(file.repo_path().display().to_string()),
+        ]),
+        None => Spans::from(file.path.display().to_string()),
+    }
+}
+
+/// A bar scaled against the file with the most churn, green for insertions
+/// and red for deletions.
+fn churn_bar<'a>(file: &FileChange, max_churn: i32) -> Spans<'a> {
+    if max_churn == 0 {
+        return Spans::default();
+    }
+
+    let width = (file.churn() * BAR_WIDTH + max_churn - 1) / max_churn;
+    let plus = if file.churn() == 0 {
+        0
+    } else {
+        (width * file.insertions + file.churn() / 2) / file.churn()
+    };
+    let minus = width - plus;
+
+    Spans::from(vec![
+        Span::styled(
+            "+".repeat(plus as usize),
+            Style::default().fg(Color::LightGreen),
+        ),
+        Span::styled(
+            "-".repeat(minus as usize),
+            Style::default().fg(Color::LightRed),
+        ),
+    ])
+}
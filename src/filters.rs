@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::git::{FileChange, GitBackend, GitResult};
+
+/// How many lines at the top of a file are searched for a generated-code marker.
+const HEADER_LINES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Matched `exclude`, or missed `include`
+    Excluded,
+    /// Marked generated in `.gitattributes` or by a header comment
+    Generated,
+    /// An untracked file larger than `untracked_max_size`
+    TooLarge,
+}
+
+impl SkipReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::Excluded => "excluded",
+            SkipReason::Generated => "generated",
+            SkipReason::TooLarge => "too large",
+        }
+    }
+}
+
+/// Decides which changed files are left out of the count.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    skip_generated: bool,
+}
+
+impl Default for PathFilter {
+    fn default() -> Self {
+        Self {
+            include: None,
+            exclude: None,
+            skip_generated: true,
+        }
+    }
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String], skip_generated: bool) -> Self {
+        Self {
+            include: build_glob_set(include),
+            exclude: build_glob_set(exclude),
+            skip_generated,
+        }
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if let Some(include) = &self.include {
+            if !include.is_match(path) {
+                return true;
+            }
+        }
+
+        self.exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(path))
+    }
+
+    /// Sets `skipped` on every file that shouldn't count. The files must all
+    /// belong to `backend`'s repository.
+    pub fn mark_skipped(
+        &self,
+        files: &mut [FileChange],
+        backend: &dyn GitBackend,
+    ) -> GitResult<()> {
+        for file in files.iter_mut() {
+            if self.is_excluded(&file.path) {
+                file.skipped = Some(SkipReason::Excluded);
+            }
+        }
+
+        if !self.skip_generated {
+            return Ok(());
+        }
+
+        // Attributes and headers are looked up in the file's own repository
+        let candidates: Vec<PathBuf> = files
+            .iter()
+            .filter(|file| file.skipped.is_none())
+            .map(|file| file.repo_path().to_path_buf())
+            .collect();
+        if candidates.is_empty() {
+            return Ok(());
+        }
+
+        let generated: HashSet<PathBuf> = backend.generated_by_attributes(&candidates)?;
+        let workdir = backend.workdir()?;
+        for file in files.iter_mut().filter(|file| file.skipped.is_none()) {
+            let repo_path = file.repo_path();
+            if generated.contains(repo_path) || has_generated_header(&workdir.join(repo_path)) {
+                file.skipped = Some(SkipReason::Generated);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Patterns without a `/` match at any depth, like in `.gitignore`.
+/// Invalid patterns are ignored.
+pub fn build_glob_set(patterns: &[String]) -> Option<GlobSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = pattern.trim_start_matches('/');
+        if let Ok(glob) = Glob::new(pattern) {
+            builder.add(glob);
+        }
+        if !pattern.contains('/') {
+            if let Ok(glob) = Glob::new(&format!("**/{pattern}")) {
+                builder.add(glob);
+            }
+        }
+    }
+    builder.build().ok()
+}
+
+/// Looks for the `@generated` or `DO NOT EDIT` markers code generators
+/// leave at the top of their output.
+fn has_generated_header(path: &Path) -> bool {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+
+    BufReader::new(file)
+        .split(b'\n')
+        .take(HEADER_LINES)
+        .map_while(Result::ok)
+        .any(|line| {
+            let line = String::from_utf8_lossy(&line);
+            line.contains("@generated") || line.contains("DO NOT EDIT")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn patterns_without_a_slash_match_at_any_depth() {
+        let filter = PathFilter::new(&[], &patterns(&["Cargo.lock", "*.snap"]), true);
+
+        assert!(filter.is_excluded(Path::new("Cargo.lock")));
+        assert!(filter.is_excluded(Path::new("crates/core/Cargo.lock")));
+        assert!(filter.is_excluded(Path::new("tests/snapshots/ui.snap")));
+        assert!(!filter.is_excluded(Path::new("src/main.rs")));
+    }
+
+    #[test]
+    fn include_limits_counting_to_matching_paths() {
+        let filter = PathFilter::new(&patterns(&["src/**"]), &patterns(&["src/gen/**"]), true);
+
+        assert!(!filter.is_excluded(Path::new("src/main.rs")));
+        assert!(filter.is_excluded(Path::new("docs/guide.md")));
+        assert!(filter.is_excluded(Path::new("src/gen/schema.rs")));
+    }
+}
@@ -1,163 +1,1429 @@
 use std::error::Error;
-use std::process::Command;
-use std::str;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 
+use chrono::Local;
 use futures::FutureExt;
 use futures_timer::Delay;
-use regex::Regex;
-use tokio::sync::mpsc::Sender;
+use serde_derive::Deserialize;
+use tokio::select;
+use tokio::sync::mpsc::{Receiver, Sender};
 
-use crate::{ManagerMessage, Options};
+use crate::adaptive::AdaptiveSettings;
+use crate::filters::{PathFilter, SkipReason};
+pub use crate::git::backend::{
+    open_backend, BackendKind, CommandOutcome, DiffTarget, GitBackend, Vcs,
+};
+pub use crate::git::branch::{BranchState, MergeBase, Upstream};
+#[cfg(test)]
+pub use crate::git::mock::MockBackend;
+pub use crate::git::patch::FilePatch;
+use crate::git::snapshot::Snapshot;
+use crate::operations::{self, Operation};
+use crate::reminders::{Reminder, ReminderSettings};
+use crate::scoring::Weights;
+use crate::snapshots::SnapshotSettings;
+use crate::watcher::{self, RefreshMode, Watch};
+use crate::{ManagerMessage, Options, RepoOptions};
 
+mod backend;
+mod branch;
+mod cli;
+mod hg;
+pub mod hooks;
+mod jj;
+pub mod log;
+#[cfg(test)]
+mod mock;
+mod native;
+mod patch;
+pub mod snapshot;
+mod stat;
+
+pub type GitResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Untracked files larger than this (in bytes) aren't counted.
+pub const DEFAULT_UNTRACKED_MAX_SIZE: u64 = 1024 * 1024;
+
+/// How long to wait for a burst of filesystem events to settle before recounting.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Which set of changes counts against the threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMode {
+    /// Index vs HEAD (`git diff --cached`)
+    Staged,
+    /// Worktree vs index (`git diff`)
+    Unstaged,
+    /// Worktree vs HEAD (`git diff HEAD`)
+    #[default]
+    Combined,
+}
+
+impl FromStr for CountMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "staged" => Ok(CountMode::Staged),
+            "unstaged" => Ok(CountMode::Unstaged),
+            "combined" => Ok(CountMode::Combined),
+            other => Err(format!("unknown count mode: {other}")),
+        }
+    }
+}
+
+impl CountMode {
+    /// Untracked files can't be staged, so they only count towards the
+    /// worktree's changes.
+    pub fn counts_untracked(self) -> bool {
+        self != CountMode::Staged
+    }
+}
+
+impl fmt::Display for CountMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CountMode::Staged => "staged",
+            CountMode::Unstaged => "unstaged",
+            CountMode::Combined => "combined",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Whether there's anything to count yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepoStatus {
+    /// A working tree with at least one commit
+    #[default]
+    Ready,
+    /// No repository was found in or above the current directory
+    NotARepository,
+    /// `git init` has run but nothing has been committed
+    UnbornBranch,
+    /// A repository without a working tree
+    Bare,
+    /// Counting failed; `GitState::update_error` says why
+    Failed,
+    /// Hasn't been counted yet
+    Waiting,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStat {
+    pub insertions: i32,
+    pub deletions: i32,
+    pub total: i32,
+}
+
+impl DiffStat {
+    pub fn new(insertions: i32, deletions: i32) -> Self {
+        Self {
+            insertions,
+            deletions,
+            total: insertions + deletions,
+        }
+    }
+
+    /// Sums the files that aren't skipped.
+    pub fn from_files(files: &[FileChange]) -> Self {
+        let counted = || files.iter().filter(|file| file.skipped.is_none());
+        let insertions = counted().map(|file| file.insertions).sum();
+        let deletions = counted().map(|file| file.deletions).sum();
+        Self::new(insertions, deletions)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+    Unmerged,
+    Untracked,
+}
+
+impl FileStatus {
+    /// The letter `git status --short` uses for this status.
+    pub fn code(&self) -> char {
+        match self {
+            FileStatus::Added => 'A',
+            FileStatus::Modified => 'M',
+            FileStatus::Deleted => 'D',
+            FileStatus::Renamed => 'R',
+            FileStatus::Copied => 'C',
+            FileStatus::TypeChanged => 'T',
+            FileStatus::Unmerged => 'U',
+            FileStatus::Untracked => '?',
+        }
+    }
+
+    /// Parses the status letter from `git diff --name-status`.
+    pub fn from_code(code: char) -> Option<Self> {
+        match code {
+            'A' => Some(FileStatus::Added),
+            'M' => Some(FileStatus::Modified),
+            'D' => Some(FileStatus::Deleted),
+            'R' => Some(FileStatus::Renamed),
+            'C' => Some(FileStatus::Copied),
+            'T' => Some(FileStatus::TypeChanged),
+            'U' => Some(FileStatus::Unmerged),
+            _ => None,
+        }
+    }
+}
+
+/// One changed file. Paths are relative to the root of the working tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub insertions: i32,
+    pub deletions: i32,
+    pub status: FileStatus,
+    pub binary: bool,
+    /// Size in bytes of binary files and untracked files too large to
+    /// count, as they are in the working tree; 0 once deleted
+    pub bytes: Option<u64>,
+    pub skipped: Option<SkipReason>,
+    /// Where the submodule the file belongs to is checked out
+    pub submodule: Option<PathBuf>,
+}
+
+impl FileChange {
+    pub fn new<P: Into<PathBuf>>(
+        path: P,
+        insertions: i32,
+        deletions: i32,
+        status: FileStatus,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            insertions,
+            deletions,
+            status,
+            binary: false,
+            bytes: None,
+            skipped: None,
+            submodule: None,
+        }
+    }
+
+    pub fn churn(&self) -> i32 {
+        self.insertions + self.deletions
+    }
+
+    /// Attributes the change to the submodule checked out at `submodule`,
+    /// prefixing its path with the submodule's.
+    pub fn in_submodule(mut self, submodule: &Path) -> Self {
+        self.path = submodule.join(&self.path);
+        self.submodule = Some(submodule.to_path_buf());
+        self
+    }
+
+    /// The path relative to the repository the file belongs to.
+    pub fn repo_path(&self) -> &Path {
+        self.submodule
+            .as_ref()
+            .and_then(|submodule| self.path.strip_prefix(submodule).ok())
+            .unwrap_or(&self.path)
+    }
+}
+
+/// Files counted by size rather than lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteStat {
+    pub files: i32,
+    pub bytes: u64,
+}
+
+impl ByteStat {
+    pub fn from_files<'a>(files: impl IntoIterator<Item = &'a FileChange>) -> Self {
+        files.into_iter().fold(Self::default(), |stat, file| Self {
+            files: stat.files + 1,
+            bytes: stat.bytes + file.bytes.unwrap_or(0),
+        })
+    }
+}
+
+/// `insertions` and `deletions` hold whichever of the staged, unstaged or
+/// combined counts was selected by the `count` option. `total` adds the
+/// lines of untracked files on top of those, unless only staged changes
+/// count. `untracked` holds them either way. Lines in skipped files are
+/// left out of every count and tallied in `skipped` instead.
+///
+/// `score` is `total` with the configured weights applied, and is what the
+/// threshold is compared against.
+///
+/// When whitespace is ignored, `total_with_whitespace` holds what `total`
+/// would have been without ignoring it.
+///
+/// Binary files have no lines, so `binary` tallies the counted ones by
+/// size instead. `too_large` holds the untracked files skipped for being
+/// larger than `untracked_max_size`.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct GitChanges {
     pub insertions: i32,
     pub deletions: i32,
     pub total: i32,
+    pub score: f64,
+    pub staged: DiffStat,
+    pub unstaged: DiffStat,
+    pub combined: DiffStat,
+    pub untracked: i32,
+    pub skipped: i32,
+    pub total_with_whitespace: Option<i32>,
+    pub binary: ByteStat,
+    pub too_large: ByteStat,
 }
 
 impl GitChanges {
+    pub fn new(
+        staged: DiffStat,
+        unstaged: DiffStat,
+        combined: DiffStat,
+        untracked: i32,
+        skipped: i32,
+        mode: CountMode,
+    ) -> Self {
+        let counted = match mode {
+            CountMode::Staged => staged,
+            CountMode::Unstaged => unstaged,
+            CountMode::Combined => combined,
+        };
+        let counted_untracked = if mode.counts_untracked() {
+            untracked
+        } else {
+            0
+        };
+
+        Self {
+            insertions: counted.insertions,
+            deletions: counted.deletions,
+            total: counted.total + counted_untracked,
+            score: f64::from(counted.total + counted_untracked),
+            staged,
+            unstaged,
+            combined,
+            untracked,
+            skipped,
+            total_with_whitespace: None,
+            binary: ByteStat::default(),
+            too_large: ByteStat::default(),
+        }
+    }
+
     pub fn compare(&self, other: &GitChanges) -> bool {
-        self.insertions == other.insertions && self.deletions == other.deletions
+        self.insertions == other.insertions
+            && self.deletions == other.deletions
+            && self.staged == other.staged
+            && self.unstaged == other.unstaged
+            && self.combined == other.combined
+            && self.untracked == other.untracked
+            && self.skipped == other.skipped
+            && self.score == other.score
+            && self.total_with_whitespace == other.total_with_whitespace
+            && self.binary == other.binary
+            && self.too_large == other.too_large
+    }
+}
+
+/// How much the branch has grown since it forked from the base branch:
+/// its commits plus the uncommitted changes, untracked lines included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchSize {
+    /// The base branch, like `main`
+    pub base: String,
+    /// Lines in skipped files are left out
+    pub changes: DiffStat,
+    /// `changes` with the configured weights applied
+    pub score: f64,
+    /// When the merge base was committed, in seconds since the epoch
+    pub since: i64,
+}
+
+/// The changed files, from each side of the index.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChangedFiles {
+    pub staged: Vec<FileChange>,
+    pub unstaged: Vec<FileChange>,
+    pub combined: Vec<FileChange>,
+    pub untracked: Vec<FileChange>,
+}
+
+impl ChangedFiles {
+    /// The files `mode` counts, followed by untracked files when it counts
+    /// them.
+    pub fn counted(&self, mode: CountMode) -> Vec<FileChange> {
+        let target = match mode {
+            CountMode::Staged => &self.staged,
+            CountMode::Unstaged => &self.unstaged,
+            CountMode::Combined => &self.combined,
+        };
+        let untracked = if mode.counts_untracked() {
+            &self.untracked[..]
+        } else {
+            &[]
+        };
+        target.iter().chain(untracked).cloned().collect()
     }
 }
 
+/// Everything that decides what gets counted.
 #[derive(Debug, Clone)]
+pub struct CountSettings {
+    pub mode: CountMode,
+    pub untracked_max_size: u64,
+    pub filter: PathFilter,
+    pub weights: Weights,
+    pub ignore_whitespace: bool,
+    /// Count the changes inside submodules too
+    pub submodules: bool,
+    /// Which submodules to recurse into, by path
+    pub submodule_filter: PathFilter,
+    /// The branch to measure the current branch's size against
+    pub base_branch: Option<String>,
+}
+
+impl Default for CountSettings {
+    fn default() -> Self {
+        Self {
+            mode: CountMode::default(),
+            untracked_max_size: DEFAULT_UNTRACKED_MAX_SIZE,
+            filter: PathFilter::default(),
+            weights: Weights::default(),
+            ignore_whitespace: false,
+            submodules: false,
+            submodule_filter: PathFilter::default(),
+            base_branch: None,
+        }
+    }
+}
+
+impl CountSettings {
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            mode: options.count_mode,
+            untracked_max_size: options.untracked_max_size,
+            filter: PathFilter::new(&options.include, &options.exclude, options.skip_generated),
+            weights: Weights::new(
+                options.insertion_weight,
+                options.deletion_weight,
+                &options.weights,
+            ),
+            ignore_whitespace: options.ignore_whitespace,
+            submodules: options.submodules,
+            submodule_filter: PathFilter::new(
+                &options.submodule_include,
+                &options.submodule_exclude,
+                false,
+            ),
+            base_branch: Some(options.base_branch.clone()).filter(|base| !base.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct GitState {
     pub git_changes: GitChanges,
+    /// The counted files, followed by untracked files
+    pub files: Vec<FileChange>,
+    /// Every changed file, whichever are counted
+    pub changed_files: ChangedFiles,
     pub current_commit: String,
     pub current_commit_short: String,
     pub last_commit: Option<String>,
     pub last_commit_short: Option<String>,
     pub threshold: i32,
+    pub count_mode: CountMode,
+    pub backend: &'static str,
+    pub vcs: Vcs,
+    pub refresh_mode: RefreshMode,
+    pub repo_status: RepoStatus,
+    /// The working tree, the git directory of a bare repository, or the
+    /// configured path while no repository has been found
+    pub repo_root: PathBuf,
+    pub branch: BranchState,
+    /// `None` when the base branch is missing or checked out, or it
+    /// couldn't be measured
+    pub branch_size: Option<BranchSize>,
+    /// Why the branch couldn't be measured
+    pub branch_size_error: Option<String>,
+    /// Why the last update failed
+    pub update_error: Option<String>,
+    /// When HEAD was committed, in seconds since the epoch
+    pub head_commit_time: Option<i64>,
+    /// When the worktree became dirty, in seconds since the epoch
+    pub dirty_since: Option<i64>,
+    /// A merge, rebase or similar waiting to be finished
+    pub operation: Option<Operation>,
+    /// Changed files that still have conflict markers
+    pub conflicts: Vec<PathBuf>,
+    /// Reminders due besides the threshold
+    pub reminders: Vec<Reminder>,
+    /// WIP snapshots, newest first; only read when snapshots are on
+    pub snapshots: Vec<Snapshot>,
 }
 
-impl Default for GitState {
-    fn default() -> Self {
+impl GitState {
+    pub fn new(threshold: i32) -> Self {
         Self {
-            git_changes: count_changes().unwrap_or_default(),
-            current_commit: get_current_commit().unwrap(),
-            current_commit_short: get_current_commit_short().unwrap(),
-            last_commit: None,
-            last_commit_short: None,
-            threshold: 100,
+            threshold,
+            ..Self::default()
         }
     }
-}
 
-impl GitState {
-    pub fn new(threshold: i32) -> Self {
+    /// Stands in for a repository until its first update arrives.
+    pub fn waiting(repo: &RepoOptions) -> Self {
         Self {
-            threshold,
+            threshold: repo.threshold,
+            repo_root: repo.path.clone(),
+            repo_status: RepoStatus::Waiting,
             ..Self::default()
         }
     }
 
-    pub fn update(&mut self) {
-        self.last_commit = Some(self.current_commit.clone());
-        self.last_commit_short = Some(self.current_commit_short.clone());
-        self.current_commit_short = get_current_commit_short().unwrap();
-        self.current_commit = get_current_commit().unwrap();
-        self.git_changes = count_changes().unwrap();
+    pub fn update(&mut self, backend: &dyn GitBackend, settings: &CountSettings) -> GitResult<()> {
+        let current_commit = backend.current_commit()?;
+        let current_commit_short = backend.current_commit_short()?;
+        let branch = backend.branch()?;
+        let head_commit_time = backend.head_commit_time()?;
+        let operation = operations::in_progress(&backend.git_dir()?);
+        let (git_changes, changed_files) = count_changes(backend, settings)?;
+        let files = changed_files.counted(settings.mode);
+        // The branch size is extra, so failing to measure it, e.g. for a
+        // base branch that doesn't resolve, leaves it out instead of
+        // failing the whole update
+        let (branch_size, branch_size_error) =
+            match branch_size(backend, settings, &branch, &changed_files.untracked) {
+                Ok(branch_size) => (branch_size, None),
+                Err(error) => (None, Some(error.to_string())),
+            };
+        let conflicts = operations::files_with_conflict_markers(&files, &backend.workdir()?);
+
+        let now = Local::now().timestamp();
+        let is_dirty = git_changes.combined.total > 0 || git_changes.untracked > 0;
+        self.dirty_since = match self.dirty_since {
+            _ if !is_dirty => None,
+            Some(since) if current_commit == self.current_commit => Some(since),
+            // Newly dirty, or still dirty after a commit
+            _ => Some(first_dirty_time(
+                &files,
+                &backend.workdir()?,
+                head_commit_time,
+                now,
+            )),
+        };
+
+        if !self.current_commit.is_empty() {
+            self.last_commit = Some(self.current_commit.clone());
+            self.last_commit_short = Some(self.current_commit_short.clone());
+        }
+        self.current_commit = current_commit;
+        self.current_commit_short = current_commit_short;
+        self.branch = branch;
+        self.branch_size = branch_size;
+        self.branch_size_error = branch_size_error;
+        self.head_commit_time = Some(head_commit_time);
+        self.operation = operation;
+        self.conflicts = conflicts;
+        self.git_changes = git_changes;
+        self.files = files;
+        self.changed_files = changed_files;
+        self.count_mode = settings.mode;
+        self.backend = backend.name();
+        self.vcs = backend.vcs();
+        self.repo_status = RepoStatus::Ready;
+        self.update_error = None;
+        Ok(())
+    }
+
+    /// Clears the counts while there's no repository to count in.
+    pub fn set_unavailable(&mut self, repo_status: RepoStatus) {
+        self.git_changes = GitChanges::default();
+        self.files.clear();
+        self.changed_files = ChangedFiles::default();
+        self.current_commit.clear();
+        self.current_commit_short.clear();
+        self.branch = BranchState::default();
+        self.branch_size = None;
+        self.branch_size_error = None;
+        self.head_commit_time = None;
+        self.dirty_since = None;
+        self.operation = None;
+        self.conflicts.clear();
+        self.reminders.clear();
+        self.snapshots.clear();
+        self.repo_status = repo_status;
+        self.update_error = None;
+    }
+
+    /// Clears the counts and keeps why they couldn't be read, to show in
+    /// their place.
+    pub fn set_failed(&mut self, error: String) {
+        self.set_unavailable(RepoStatus::Failed);
+        self.update_error = Some(error);
     }
 
     pub fn is_above_threshold(&self) -> bool {
-        self.git_changes.total > self.threshold
+        self.git_changes.score > f64::from(self.threshold)
+    }
+
+    /// Whether the bell should ring for this repository. It stays quiet
+    /// while a merge, rebase or the like is in progress, since those changes
+    /// can't be committed piecemeal.
+    pub fn wants_attention(&self) -> bool {
+        self.operation.is_none() && (self.is_above_threshold() || !self.reminders.is_empty())
     }
 
     pub fn compare(&self, other: &Self) -> bool {
+        if self.repo_status != other.repo_status
+            || self.repo_root != other.repo_root
+            || self.update_error != other.update_error
+        {
+            return false;
+        }
         if self.current_commit != other.current_commit {
             return false;
         }
         if self.last_commit != other.last_commit {
             return false;
         }
+        if self.operation != other.operation || self.conflicts != other.conflicts {
+            return false;
+        }
+        if self.snapshots != other.snapshots {
+            return false;
+        }
+        if self.branch != other.branch
+            || self.branch_size != other.branch_size
+            || self.branch_size_error != other.branch_size_error
+        {
+            return false;
+        }
+        if self.reminders != other.reminders {
+            return false;
+        }
+        if self.head_commit_time != other.head_commit_time || self.dirty_since != other.dirty_since
+        {
+            return false;
+        }
+
+        self.git_changes.compare(&other.git_changes) && self.changed_files == other.changed_files
+    }
+}
+
+/// A change to make to a repository, run by its `git_loop`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitCommand {
+    Commit {
+        message: String,
+        all: bool,
+    },
+    Stage {
+        file: FileChange,
+    },
+    Unstage {
+        file: FileChange,
+    },
+    /// Reads a file's hunks for the hunk view, unstaged or `staged`
+    LoadPatch {
+        file: FileChange,
+        staged: bool,
+    },
+    /// Stages a hunk of `patch`, or unstages it when `staged` is set
+    ApplyHunk {
+        file: FileChange,
+        patch: FilePatch,
+        hunk: usize,
+        staged: bool,
+    },
+    TakeSnapshot,
+    LoadSnapshotDiff {
+        name: String,
+    },
+    RestoreSnapshot {
+        name: String,
+    },
+}
+
+impl GitCommand {
+    /// Runs the command, returning the message that reports how it went.
+    /// Snapshots taken in the background aren't reported; they show up in
+    /// the next state.
+    fn run(self, backend: Option<&dyn GitBackend>, repo: usize) -> Option<ManagerMessage> {
+        let Some(backend) = backend else {
+            let outcome = CommandOutcome::failed("not a git repository");
+            return Some(ManagerMessage::CommandFinished { repo, outcome });
+        };
+
+        let result = match self {
+            GitCommand::LoadPatch { file, staged } => {
+                let patch = in_file_repo(backend, &file, |backend, path| {
+                    backend.file_patch(path, staged)
+                });
+                return Some(ManagerMessage::Patch {
+                    repo,
+                    path: file.path,
+                    staged,
+                    patch: patch.map_err(|error| error.to_string()),
+                });
+            }
+            GitCommand::TakeSnapshot => {
+                let _ = take_snapshot(backend);
+                return None;
+            }
+            GitCommand::LoadSnapshotDiff { name } => {
+                let diff = backend
+                    .workdir()
+                    .and_then(|workdir| snapshot::diff(&workdir, &name))
+                    .unwrap_or_else(|error| error.to_string());
+                return Some(ManagerMessage::SnapshotDiff { repo, name, diff });
+            }
+            GitCommand::RestoreSnapshot { name } => backend
+                .workdir()
+                .and_then(|workdir| snapshot::restore(&workdir, &name)),
+            GitCommand::Commit { message, all } => backend.commit(&message, all),
+            GitCommand::Stage { file } => {
+                in_file_repo(backend, &file, |backend, path| backend.stage(path))
+            }
+            GitCommand::Unstage { file } => {
+                in_file_repo(backend, &file, |backend, path| backend.unstage(path))
+            }
+            GitCommand::ApplyHunk {
+                file,
+                patch,
+                hunk,
+                staged,
+            } => in_file_repo(backend, &file, |backend, _| {
+                backend.apply_hunk(&patch, hunk, staged)
+            }),
+        };
+        let outcome = result.unwrap_or_else(|error| CommandOutcome::failed(error.to_string()));
+        Some(ManagerMessage::CommandFinished { repo, outcome })
+    }
+}
+
+fn take_snapshot(backend: &dyn GitBackend) -> GitResult<Option<Snapshot>> {
+    snapshot::create(&backend.workdir()?, Local::now())
+}
+
+/// Runs `f` in the repository `file` belongs to, with the file's path there.
+fn in_file_repo<T>(
+    backend: &dyn GitBackend,
+    file: &FileChange,
+    f: impl FnOnce(&dyn GitBackend, &Path) -> GitResult<T>,
+) -> GitResult<T> {
+    match &file.submodule {
+        Some(submodule) => f(
+            backend.open_submodule(submodule)?.as_ref(),
+            file.repo_path(),
+        ),
+        None => f(backend, &file.path),
+    }
+}
+
+/// Counts the changes in `options.repos[repo]` until the app quits, running
+/// the commands sent to it in between.
+pub async fn git_loop(
+    tx: Sender<ManagerMessage>,
+    options: Arc<Options>,
+    repo: usize,
+    mut commands: Receiver<GitCommand>,
+) {
+    let repo_options = &options.repos[repo];
+    let loop_time = Duration::from_millis(options.git_update_time);
+    let safety_interval = Duration::from_millis(options.safety_interval);
+    let settings = CountSettings::from_options(&options);
+    let reminder_settings = ReminderSettings::from_options(&options);
+    let snapshot_settings = SnapshotSettings::from_options(&options);
+    let adaptive_settings = AdaptiveSettings::from_options(&options);
+    let mut git_state = GitState::new(repo_options.threshold);
+    // The commit the adaptive threshold was last worked out at
+    let mut adapted_at: Option<String> = None;
+    let mut backend: Option<Box<dyn GitBackend>> = None;
+    // Keeps the watch alive while the repository stays valid
+    let mut watch: Option<Watch> = None;
+    // Set once the system runs out of file watches, to stop trying
+    let mut out_of_watches = false;
+
+    loop {
+        // Keep looking until a repository shows up, e.g. after `git init`
+        if backend.is_none() {
+            backend = open_backend(options.backend, &repo_options.path).ok();
+            git_state.repo_root = backend
+                .as_deref()
+                .and_then(repo_root)
+                .unwrap_or_else(|| repo_options.path.clone());
+        }
+        let repo_status = backend
+            .as_ref()
+            .and_then(|backend| backend.repo_status().ok())
+            .unwrap_or(RepoStatus::NotARepository);
+
+        match backend.as_deref() {
+            Some(backend) if repo_status == RepoStatus::Ready => {
+                if options.watch && watch.is_none() && !out_of_watches {
+                    match start_watcher(backend) {
+                        Ok(started) => watch = Some(started),
+                        Err(error) => out_of_watches = watcher::is_watch_limit(&error),
+                    }
+                }
+                git_state.refresh_mode = match watch {
+                    Some(_) => RefreshMode::Watching,
+                    None if out_of_watches => RefreshMode::WatchLimit,
+                    None => RefreshMode::Polling,
+                };
+                match git_state.update(backend, &settings) {
+                    Ok(()) => {
+                        // Each commit adds to the history, so adapt again once HEAD moves
+                        if adaptive_settings.enabled
+                            && backend.vcs() == Vcs::Git
+                            && adapted_at.as_ref() != Some(&git_state.current_commit)
+                        {
+                            git_state.threshold = backend
+                                .workdir()
+                                .ok()
+                                .and_then(|workdir| {
+                                    adaptive_settings.threshold(&workdir, &settings)
+                                })
+                                .unwrap_or(repo_options.threshold);
+                            adapted_at = Some(git_state.current_commit.clone());
+                        }
+                        let now = Local::now();
+                        git_state.reminders = reminder_settings.check(&git_state, now);
+                        if snapshot_settings.enabled {
+                            if snapshot_settings.is_due(&git_state, now) {
+                                let _ = take_snapshot(backend);
+                            }
+                            git_state.snapshots = backend
+                                .workdir()
+                                .and_then(|workdir| snapshot::list(&workdir))
+                                .unwrap_or_default();
+                        }
+                    }
+                    // Said in place of the counts, instead of going quiet
+                    Err(error) => git_state.set_failed(error.to_string()),
+                }
+            }
+            _ => {
+                if repo_status == RepoStatus::NotARepository {
+                    backend = None;
+                }
+                watch = None;
+                git_state.refresh_mode = RefreshMode::Polling;
+                git_state.set_unavailable(repo_status);
+            }
+        }
 
-        self.git_changes.compare(&other.git_changes)
+        let message = ManagerMessage::Git {
+            repo,
+            git_state: Box::new(git_state.clone()),
+        };
+        tx.send(message).await.unwrap();
+
+        let command = match watch.as_mut() {
+            Some(watching) => {
+                select! {
+                    Some(command) = commands.recv() => Some(command),
+                    _ = watching.events.recv() => {
+                        // Let a burst of writes settle, then drop the events it queued
+                        Delay::new(WATCH_DEBOUNCE).fuse().await;
+                        while watching.events.try_recv().is_ok() {}
+                        None
+                    }
+                    _ = Delay::new(safety_interval).fuse() => None,
+                }
+            }
+            None => {
+                select! {
+                    Some(command) = commands.recv() => Some(command),
+                    _ = Delay::new(loop_time).fuse() => None,
+                }
+            }
+        };
+
+        // A watcher that can't take on the new directories is started afresh,
+        // unless the system is out of watches
+        if let Some(Err(error)) = watch.as_mut().map(Watch::watch_new_dirs) {
+            out_of_watches = watcher::is_watch_limit(&error);
+            watch = None;
+        }
+
+        // Runs the command, then loops straight back round to recount
+        if let Some(message) = command.and_then(|command| command.run(backend.as_deref(), repo)) {
+            tx.send(message).await.unwrap();
+        }
     }
+}
+
+fn repo_root(backend: &dyn GitBackend) -> Option<PathBuf> {
+    let root = backend.workdir().or_else(|_| backend.git_dir()).ok()?;
+    // Drops the trailing slash libgit2 leaves on the working tree
+    Some(root.components().collect())
+}
+
+fn start_watcher(backend: &dyn GitBackend) -> notify::Result<Watch> {
+    let paths = backend
+        .workdir()
+        .and_then(|workdir| Ok((workdir, backend.git_dir()?)));
+    let (workdir, git_dir) = paths.map_err(|error| notify::Error::generic(&error.to_string()))?;
+    watcher::watch(&workdir, &git_dir)
+}
 
-    pub fn compare_with_prev(&self, prev: Arc<Option<GitState>>) -> bool {
-        if let Some(prev) = prev.as_ref() {
-            self.compare(prev)
+/// Returns the changes along with the files behind the counted numbers.
+pub fn count_changes(
+    backend: &dyn GitBackend,
+    settings: &CountSettings,
+) -> GitResult<(GitChanges, ChangedFiles)> {
+    let submodules = if settings.submodules {
+        find_submodules(backend, settings, Path::new(""))?
+    } else {
+        Vec::new()
+    };
+    let changed_files = |target, ignore_whitespace| {
+        collect_files(backend, &submodules, settings, |backend| {
+            backend.file_changes(target, ignore_whitespace)
+        })
+    };
+
+    let ignore_whitespace = settings.ignore_whitespace;
+    let staged = changed_files(DiffTarget::Staged, ignore_whitespace)?;
+    let unstaged = changed_files(DiffTarget::Unstaged, ignore_whitespace)?;
+    let combined = changed_files(DiffTarget::Combined, ignore_whitespace)?;
+    let untracked = collect_files(backend, &submodules, settings, |backend| {
+        backend.untracked_files(settings.untracked_max_size)
+    })?;
+
+    let staged_stat = DiffStat::from_files(&staged);
+    let unstaged_stat = DiffStat::from_files(&unstaged);
+    let combined_stat = DiffStat::from_files(&combined);
+    let untracked_lines = DiffStat::from_files(&untracked).insertions;
+
+    let counted_target = match settings.mode {
+        CountMode::Staged => DiffTarget::Staged,
+        CountMode::Unstaged => DiffTarget::Unstaged,
+        CountMode::Combined => DiffTarget::Combined,
+    };
+    let changed_files_by_target = ChangedFiles {
+        staged,
+        unstaged,
+        combined,
+        untracked,
+    };
+    let files = changed_files_by_target.counted(settings.mode);
+
+    let skipped = files
+        .iter()
+        .filter(|file| file.skipped.is_some())
+        .map(|file| file.churn())
+        .sum();
+
+    let mut git_changes = GitChanges::new(
+        staged_stat,
+        unstaged_stat,
+        combined_stat,
+        untracked_lines,
+        skipped,
+        settings.mode,
+    );
+    git_changes.score = settings.weights.score_files(&files);
+    git_changes.binary = ByteStat::from_files(
+        files
+            .iter()
+            .filter(|file| file.binary && file.skipped.is_none()),
+    );
+    git_changes.too_large = ByteStat::from_files(
+        files
+            .iter()
+            .filter(|file| file.skipped == Some(SkipReason::TooLarge)),
+    );
+
+    if ignore_whitespace {
+        let with_whitespace = changed_files(counted_target, false)?;
+        let counted_untracked = if settings.mode.counts_untracked() {
+            untracked_lines
         } else {
-            false
+            0
+        };
+        git_changes.total_with_whitespace =
+            Some(DiffStat::from_files(&with_whitespace).total + counted_untracked);
+    }
+
+    Ok((git_changes, changed_files_by_target))
+}
+
+/// The staged changes alone, with skipped files marked, as a commit made
+/// now would record them. Submodules commit their own changes, so they're
+/// left out.
+pub fn staged_changes(
+    backend: &dyn GitBackend,
+    settings: &CountSettings,
+) -> GitResult<Vec<FileChange>> {
+    collect_files(backend, &[], settings, |backend| {
+        backend.file_changes(DiffTarget::Staged, settings.ignore_whitespace)
+    })
+}
+
+/// Measures the branch against `settings.base_branch`. Submodules aren't
+/// included. `None` when there's no base branch to measure against, or
+/// it's the branch that's checked out.
+pub fn branch_size(
+    backend: &dyn GitBackend,
+    settings: &CountSettings,
+    branch: &BranchState,
+    untracked: &[FileChange],
+) -> GitResult<Option<BranchSize>> {
+    let Some(base) = &settings.base_branch else {
+        return Ok(None);
+    };
+    if branch.name.as_ref() == Some(base) {
+        return Ok(None);
+    }
+    let Some(merge_base) = backend.merge_base(base)? else {
+        return Ok(None);
+    };
+
+    let mut files = backend.changes_since(&merge_base.commit, settings.ignore_whitespace)?;
+    settings.filter.mark_skipped(&mut files, backend)?;
+    files.extend(
+        untracked
+            .iter()
+            .filter(|file| file.submodule.is_none())
+            .cloned(),
+    );
+
+    Ok(Some(BranchSize {
+        base: base.clone(),
+        changes: DiffStat::from_files(&files),
+        score: settings.weights.score_files(&files),
+        since: merge_base.time,
+    }))
+}
+
+/// A checked-out submodule counted along with its superproject.
+struct Submodule {
+    /// Relative to the superproject's working tree
+    path: PathBuf,
+    backend: Box<dyn GitBackend>,
+}
+
+/// Finds the submodules to count, including nested ones. Submodules that
+/// aren't checked out are left out.
+fn find_submodules(
+    backend: &dyn GitBackend,
+    settings: &CountSettings,
+    prefix: &Path,
+) -> GitResult<Vec<Submodule>> {
+    let mut submodules = Vec::new();
+    for path in backend.submodules()? {
+        let full_path = prefix.join(&path);
+        if settings.submodule_filter.is_excluded(&full_path) {
+            continue;
         }
+        let Ok(submodule_backend) = backend.open_submodule(&path) else {
+            continue;
+        };
+
+        let nested = find_submodules(submodule_backend.as_ref(), settings, &full_path)?;
+        submodules.push(Submodule {
+            path: full_path,
+            backend: submodule_backend,
+        });
+        submodules.extend(nested);
     }
+    Ok(submodules)
 }
 
-pub async fn git_loop(tx: Sender<ManagerMessage>, options: Arc<Options>) {
-    let threshold = options.threshold;
-    let loop_time = options.git_update_time;
-    loop {
-        let mut git_state = GitState::new(threshold);
-        git_state.update();
+/// Lists files in the repository and its submodules, with skipped files marked.
+fn collect_files<F>(
+    backend: &dyn GitBackend,
+    submodules: &[Submodule],
+    settings: &CountSettings,
+    list: F,
+) -> GitResult<Vec<FileChange>>
+where
+    F: Fn(&dyn GitBackend) -> GitResult<Vec<FileChange>>,
+{
+    let mut files = list(backend)?;
+    measure_binary_files(&mut files, &backend.workdir()?);
+    settings.filter.mark_skipped(&mut files, backend)?;
+
+    for submodule in submodules {
+        let mut submodule_files = list(submodule.backend.as_ref())?;
+        measure_binary_files(&mut submodule_files, &submodule.backend.workdir()?);
+        let mut submodule_files: Vec<FileChange> = submodule_files
+            .into_iter()
+            .map(|file| file.in_submodule(&submodule.path))
+            .collect();
+        settings
+            .filter
+            .mark_skipped(&mut submodule_files, submodule.backend.as_ref())?;
+        files.extend(submodule_files);
+    }
+    Ok(files)
+}
 
-        let message = ManagerMessage::Git { git_state };
+/// Sets the size of binary files that don't have one yet from the working
+/// tree. Diffs don't carry sizes, so the working tree stands in for the
+/// index too.
+fn measure_binary_files(files: &mut [FileChange], workdir: &Path) {
+    for file in files
+        .iter_mut()
+        .filter(|file| file.binary && file.bytes.is_none())
+    {
+        let size = fs::metadata(workdir.join(&file.path)).map_or(0, |metadata| metadata.len());
+        file.bytes = Some(size);
+    }
+}
 
-        tx.send(message).await.unwrap();
-        Delay::new(Duration::from_millis(loop_time)).fuse().await;
-    }
-}
-
-pub fn get_current_commit() -> Result<String, Box<dyn Error>> {
-    let output = Command::new("git")
-        .arg("rev-parse")
-        .arg("HEAD")
-        .output()?
-        .stdout;
-    let output = String::from_utf8(output)?;
-    Ok(output.trim().to_string())
-}
-
-pub fn get_current_commit_short() -> Result<String, Box<dyn Error>> {
-    let output = Command::new("git")
-        .arg("rev-parse")
-        .arg("--short")
-        .arg("HEAD")
-        .output()?
-        .stdout;
-    let output = String::from_utf8(output)?;
-    Ok(output.trim().to_string())
-}
-
-pub fn count_changes() -> Result<GitChanges, Box<(dyn Error + 'static)>> {
-    let output = Command::new("git")
-        .arg("diff")
-        .arg("--shortstat")
-        .output()?;
-
-    let stdout = str::from_utf8(&output.stdout)?;
-    let re = Regex::new(r"((\d+)\D+)((\d+)\D+)?((\d+)?\D+)?")?;
-    let captures = re.captures(stdout).ok_or("No match");
-
-    match captures {
-        Ok(captures) => {
-            let insertions = captures
-                .get(4)
-                .map_or("0", |m| m.as_str())
-                .parse::<i32>()
-                .unwrap();
-            let deletions = captures
-                .get(6)
-                .map_or("0", |m| m.as_str())
-                .parse::<i32>()
-                .unwrap();
-
-            let total: i32 = insertions + deletions;
-
-            Ok(GitChanges {
-                insertions,
-                deletions,
-                total,
-            })
-        }
-
-        Err(_) => Ok(GitChanges {
-            insertions: 0,
-            deletions: 0,
-            total: 0,
-        }),
+/// Estimates when the worktree became dirty from the oldest modification
+/// time of the changed files, but no earlier than the HEAD commit.
+fn first_dirty_time(files: &[FileChange], workdir: &Path, head_commit_time: i64, now: i64) -> i64 {
+    files
+        .iter()
+        .filter_map(|file| fs::metadata(workdir.join(&file.path)).ok()?.modified().ok())
+        .filter_map(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs() as i64)
+        .min()
+        .unwrap_or(now)
+        .clamp(head_commit_time.min(now), now)
+}
+
+/// Builds the change for an untracked file, counting every line as an
+/// insertion. Files larger than `max_size` bytes aren't read; they're
+/// skipped as too large, with their size.
+pub fn untracked_file_change(path: &Path, workdir: &Path, max_size: u64) -> Option<FileChange> {
+    let full_path = workdir.join(path);
+    let metadata = fs::metadata(&full_path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+
+    let mut file_change = FileChange::new(path, 0, 0, FileStatus::Untracked);
+    if metadata.len() > max_size {
+        file_change.bytes = Some(metadata.len());
+        file_change.skipped = Some(SkipReason::TooLarge);
+        return Some(file_change);
+    }
+
+    let contents = fs::read(&full_path).ok()?;
+    if contents.iter().take(8000).any(|byte| *byte == 0) {
+        file_change.binary = true;
+        file_change.bytes = Some(metadata.len());
+        return Some(file_change);
+    }
+
+    let newlines = contents.iter().filter(|byte| **byte == b'\n').count();
+    let unterminated = !contents.is_empty() && !contents.ends_with(b"\n");
+    file_change.insertions = (newlines + usize::from(unterminated)) as i32;
+    Some(file_change)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn mock_backend() -> MockBackend {
+        MockBackend {
+            commit: "c0ffee".to_string(),
+            staged: vec![FileChange::new("src/lib.rs", 10, 2, FileStatus::Modified)],
+            unstaged: vec![
+                FileChange::new("src/lib.rs", 20, 5, FileStatus::Modified),
+                FileChange::new("README.md", 10, 0, FileStatus::Modified),
+            ],
+            combined: vec![
+                FileChange::new("src/lib.rs", 30, 7, FileStatus::Modified),
+                FileChange::new("README.md", 10, 0, FileStatus::Modified),
+            ],
+            untracked: vec![FileChange::new("src/new.rs", 20, 0, FileStatus::Untracked)],
+            ..MockBackend::default()
+        }
+    }
+
+    #[test]
+    fn counts_combined_changes_and_untracked_lines_by_default() {
+        let mut git_state = GitState::new(100);
+        git_state
+            .update(&mock_backend(), &CountSettings::default())
+            .unwrap();
+
+        assert_eq!(git_state.git_changes.insertions, 40);
+        assert_eq!(git_state.git_changes.deletions, 7);
+        assert_eq!(git_state.git_changes.total, 67);
+        assert!(!git_state.is_above_threshold());
+        assert_eq!(git_state.files.len(), 3);
+    }
+
+    #[test]
+    fn count_mode_selects_the_counted_changes() {
+        let settings = CountSettings {
+            mode: CountMode::Staged,
+            ..CountSettings::default()
+        };
+        let mut git_state = GitState::new(10);
+        git_state.update(&mock_backend(), &settings).unwrap();
+
+        assert_eq!(git_state.git_changes.total, 12);
+        assert_eq!(git_state.git_changes.unstaged, DiffStat::new(30, 5));
+        assert_eq!(git_state.files[0].path, PathBuf::from("src/lib.rs"));
+        assert!(git_state.is_above_threshold());
+    }
+
+    #[test]
+    fn untracked_lines_count_unless_only_staged_changes_do() {
+        for (mode, total) in [
+            (CountMode::Staged, 12),
+            (CountMode::Unstaged, 55),
+            (CountMode::Combined, 67),
+        ] {
+            let settings = CountSettings {
+                mode,
+                ..CountSettings::default()
+            };
+            let mut git_state = GitState::new(100);
+            git_state.update(&mock_backend(), &settings).unwrap();
+
+            assert_eq!(git_state.git_changes.total, total, "{mode}");
+            assert_eq!(git_state.git_changes.score, f64::from(total), "{mode}");
+            assert_eq!(git_state.git_changes.untracked, 20, "{mode}");
+            assert_eq!(
+                git_state
+                    .files
+                    .iter()
+                    .any(|file| file.status == FileStatus::Untracked),
+                mode.counts_untracked(),
+                "{mode}"
+            );
+        }
+    }
+
+    #[test]
+    fn excluded_and_generated_files_are_skipped() {
+        let mut backend = mock_backend();
+        backend.combined.push(FileChange::new(
+            "Cargo.lock",
+            300,
+            120,
+            FileStatus::Modified,
+        ));
+        backend.generated.insert(PathBuf::from("README.md"));
+        let settings = CountSettings {
+            filter: PathFilter::new(&[], &["Cargo.lock".to_string()], true),
+            ..CountSettings::default()
+        };
+
+        let mut git_state = GitState::new(100);
+        git_state.update(&backend, &settings).unwrap();
+
+        assert_eq!(git_state.git_changes.total, 57);
+        assert_eq!(git_state.git_changes.skipped, 430);
+        let skipped: Vec<_> = git_state
+            .files
+            .iter()
+            .map(|file| (file.path.to_str().unwrap(), file.skipped))
+            .collect();
+        assert!(skipped.contains(&("Cargo.lock", Some(SkipReason::Excluded))));
+        assert!(skipped.contains(&("README.md", Some(SkipReason::Generated))));
+    }
+
+    #[test]
+    fn threshold_is_compared_against_the_weighted_score() {
+        let settings = CountSettings {
+            weights: Weights::new(1.0, 1.0, &HashMap::from([("*.md".to_string(), 0.5)])),
+            ..CountSettings::default()
+        };
+        let mut git_state = GitState::new(60);
+        git_state.update(&mock_backend(), &settings).unwrap();
+
+        assert_eq!(git_state.git_changes.total, 67);
+        assert_eq!(git_state.git_changes.score, 62.0);
+        assert!(git_state.is_above_threshold());
+
+        git_state.threshold = 65;
+        assert!(!git_state.is_above_threshold());
+    }
+
+    #[test]
+    fn update_remembers_the_previous_commit() {
+        let mut backend = mock_backend();
+        let settings = CountSettings::default();
+        let mut git_state = GitState::new(100);
+        git_state.update(&backend, &settings).unwrap();
+        assert_eq!(git_state.last_commit, None);
+
+        let before = git_state.clone();
+        backend.commit = "f00d".to_string();
+        git_state.update(&backend, &settings).unwrap();
+
+        assert_eq!(git_state.last_commit, Some(before.current_commit.clone()));
+        assert!(!git_state.compare(&before));
+    }
+
+    #[test]
+    fn unavailable_repository_clears_the_counts() {
+        let backend = mock_backend();
+        let mut git_state = GitState::new(10);
+        git_state
+            .update(&backend, &CountSettings::default())
+            .unwrap();
+        assert!(git_state.is_above_threshold());
+
+        let before = git_state.clone();
+        git_state.set_unavailable(RepoStatus::UnbornBranch);
+
+        assert_eq!(git_state.repo_status, RepoStatus::UnbornBranch);
+        assert!(git_state.files.is_empty());
+        assert!(git_state.current_commit.is_empty());
+        assert!(!git_state.is_above_threshold());
+        assert!(!git_state.compare(&before));
+    }
+
+    #[test]
+    fn submodule_changes_are_counted_and_attributed() {
+        let mut backend = mock_backend();
+        let submodule = MockBackend {
+            commit: "5ub".to_string(),
+            combined: vec![FileChange::new("src/lib.rs", 8, 2, FileStatus::Modified)],
+            untracked: vec![FileChange::new("notes.txt", 5, 0, FileStatus::Untracked)],
+            ..MockBackend::default()
+        };
+        backend.submodules = vec![
+            (PathBuf::from("libs/core"), submodule.clone()),
+            (PathBuf::from("vendor/dep"), submodule),
+        ];
+        let settings = CountSettings {
+            submodules: true,
+            submodule_filter: PathFilter::new(&[], &["vendor/*".to_string()], false),
+            ..CountSettings::default()
+        };
+
+        let (git_changes, changed_files) = count_changes(&backend, &settings).unwrap();
+        let files = changed_files.counted(settings.mode);
+
+        assert_eq!(git_changes.total, 67 + 10 + 5);
+        let submodule_file = files
+            .iter()
+            .find(|file| file.path == Path::new("libs/core/src/lib.rs"))
+            .unwrap();
+        assert_eq!(submodule_file.submodule, Some(PathBuf::from("libs/core")));
+        assert_eq!(submodule_file.repo_path(), Path::new("src/lib.rs"));
+        assert!(!files.iter().any(|file| file.path.starts_with("vendor")));
+
+        let (git_changes, _) = count_changes(&backend, &CountSettings::default()).unwrap();
+        assert_eq!(git_changes.total, 67);
+    }
+
+    #[test]
+    fn branch_size_counts_from_the_merge_base() {
+        let mut backend = mock_backend();
+        backend.branch.name = Some("feature".to_string());
+        backend.merge_base = Some(MergeBase {
+            commit: "ba5e".to_string(),
+            time: 1_700_000_000,
+        });
+        backend.since_merge_base = vec![
+            FileChange::new("src/lib.rs", 130, 27, FileStatus::Modified),
+            FileChange::new("Cargo.lock", 50, 50, FileStatus::Modified),
+        ];
+        let settings = CountSettings {
+            base_branch: Some("main".to_string()),
+            filter: PathFilter::new(&[], &["Cargo.lock".to_string()], false),
+            ..CountSettings::default()
+        };
+
+        let mut git_state = GitState::new(100);
+        git_state.update(&backend, &settings).unwrap();
+        let branch_size = git_state.branch_size.clone().unwrap();
+
+        assert_eq!(branch_size.base, "main");
+        assert_eq!(branch_size.changes, DiffStat::new(150, 27));
+        assert_eq!(branch_size.score, 177.0);
+        assert_eq!(branch_size.since, 1_700_000_000);
+
+        // Nothing to measure on the base branch itself
+        backend.branch.name = Some("main".to_string());
+        git_state.update(&backend, &settings).unwrap();
+        assert_eq!(git_state.branch_size, None);
+    }
+
+    #[test]
+    fn failing_to_measure_the_branch_still_counts_the_changes() {
+        let mut backend = mock_backend();
+        backend.branch.name = Some("feature".to_string());
+        backend.merge_base_error = Some("unknown revision: mian".to_string());
+        let settings = CountSettings {
+            base_branch: Some("mian".to_string()),
+            ..CountSettings::default()
+        };
+
+        let mut git_state = GitState::new(100);
+        git_state.update(&backend, &settings).unwrap();
+
+        assert_eq!(git_state.branch_size, None);
+        assert_eq!(
+            git_state.branch_size_error.as_deref(),
+            Some("unknown revision: mian")
+        );
+        assert_eq!(git_state.git_changes.total, 67);
+    }
+
+    #[test]
+    fn binary_and_oversized_files_are_tallied_by_size() {
+        let mut backend = mock_backend();
+        let mut logo = FileChange::new("logo.png", 0, 0, FileStatus::Added);
+        logo.binary = true;
+        logo.bytes = Some(40_000);
+        backend.combined.push(logo);
+        let mut dataset = FileChange::new("data.csv", 0, 0, FileStatus::Untracked);
+        dataset.bytes = Some(200_000_000);
+        dataset.skipped = Some(SkipReason::TooLarge);
+        backend.untracked.push(dataset);
+
+        let (git_changes, _) = count_changes(&backend, &CountSettings::default()).unwrap();
+
+        assert_eq!(git_changes.total, 67);
+        assert_eq!(
+            git_changes.binary,
+            ByteStat {
+                files: 1,
+                bytes: 40_000
+            }
+        );
+        assert_eq!(
+            git_changes.too_large,
+            ByteStat {
+                files: 1,
+                bytes: 200_000_000
+            }
+        );
+    }
+
+    #[test]
+    fn dirty_since_is_kept_until_the_tree_is_clean() {
+        let mut backend = mock_backend();
+        let settings = CountSettings::default();
+        let mut git_state = GitState::new(100);
+        git_state.update(&backend, &settings).unwrap();
+        let dirty_since = git_state.dirty_since;
+        assert!(dirty_since.is_some());
+
+        git_state.update(&backend, &settings).unwrap();
+        assert_eq!(git_state.dirty_since, dirty_since);
+
+        backend.combined.clear();
+        backend.untracked.clear();
+        git_state.update(&backend, &settings).unwrap();
+        assert_eq!(git_state.dirty_since, None);
     }
 }
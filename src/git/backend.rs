@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde_derive::Deserialize;
+
+use crate::git::cli::CliBackend;
+use crate::git::hg::HgBackend;
+use crate::git::jj::JjBackend;
+use crate::git::native::NativeBackend;
+use crate::git::{BranchState, FileChange, FilePatch, GitResult, MergeBase, RepoStatus};
+
+/// Which side of the index a diff is taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffTarget {
+    /// Index vs HEAD
+    Staged,
+    /// Worktree vs index
+    Unstaged,
+    /// Worktree vs HEAD
+    Combined,
+}
+
+/// The repository queries diffding runs on every tick.
+pub trait GitBackend: Send {
+    /// Short name shown in the UI
+    fn name(&self) -> &'static str;
+
+    /// The version control system the repository belongs to.
+    fn vcs(&self) -> Vcs {
+        Vcs::Git
+    }
+
+    /// Root of the working tree
+    fn workdir(&self) -> GitResult<PathBuf>;
+
+    /// The `.git` directory, or `.jj` or `.hg` for those backends
+    fn git_dir(&self) -> GitResult<PathBuf>;
+
+    /// Only a `Ready` repository has changes to count.
+    fn repo_status(&self) -> GitResult<RepoStatus>;
+
+    fn current_commit(&self) -> GitResult<String>;
+
+    fn current_commit_short(&self) -> GitResult<String>;
+
+    /// When HEAD was committed, in seconds since the epoch.
+    fn head_commit_time(&self) -> GitResult<i64>;
+
+    /// The current branch and how it compares to its upstream.
+    fn branch(&self) -> GitResult<BranchState>;
+
+    /// Where HEAD forked from `base`, a branch or other revision. `None`
+    /// when `base` doesn't exist or shares no history with HEAD.
+    fn merge_base(&self, base: &str) -> GitResult<Option<MergeBase>>;
+
+    /// Changes from `commit` to the working tree, staged or not.
+    fn changes_since(&self, commit: &str, ignore_whitespace: bool) -> GitResult<Vec<FileChange>>;
+
+    /// With `ignore_whitespace`, lines that differ only in whitespace and
+    /// added or removed blank lines aren't counted (`git diff -w --ignore-blank-lines`).
+    fn file_changes(
+        &self,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> GitResult<Vec<FileChange>>;
+
+    /// Untracked, non-ignored files no larger than `max_size` bytes.
+    fn untracked_files(&self, max_size: u64) -> GitResult<Vec<FileChange>>;
+
+    /// The paths marked `linguist-generated` or `-diff` in `.gitattributes`.
+    fn generated_by_attributes(&self, paths: &[PathBuf]) -> GitResult<HashSet<PathBuf>>;
+
+    /// Where the submodules are, relative to the working tree.
+    fn submodules(&self) -> GitResult<Vec<PathBuf>>;
+
+    /// Opens the submodule at `path`. Fails if it isn't checked out.
+    fn open_submodule(&self, path: &Path) -> GitResult<Box<dyn GitBackend>>;
+
+    /// Runs `git commit` with `message`, staging everything first when
+    /// `all` is set. Hooks run as usual; a rejected commit isn't an error.
+    fn commit(&self, message: &str, all: bool) -> GitResult<CommandOutcome>;
+
+    /// Stages all of `path`'s changes, like `git add`.
+    fn stage(&self, path: &Path) -> GitResult<CommandOutcome>;
+
+    /// Takes `path` back out of the index, like `git reset`.
+    fn unstage(&self, path: &Path) -> GitResult<CommandOutcome>;
+
+    /// `path`'s unstaged diff, or its staged one when `staged` is set.
+    fn file_patch(&self, path: &Path, staged: bool) -> GitResult<FilePatch>;
+
+    /// Stages hunk `hunk` of `patch`, or unstages it when `staged` is set
+    /// and the patch came from the index.
+    fn apply_hunk(&self, patch: &FilePatch, hunk: usize, staged: bool)
+        -> GitResult<CommandOutcome>;
+}
+
+/// How a command that changes the repository went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutcome {
+    pub success: bool,
+    /// Everything git and its hooks printed
+    pub output: String,
+}
+
+impl CommandOutcome {
+    pub fn failed(output: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            output: output.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// libgit2, in-process
+    #[default]
+    Native,
+    /// The `git` command line
+    Cli,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "native" => Ok(BackendKind::Native),
+            "cli" => Ok(BackendKind::Cli),
+            other => Err(format!("unknown backend: {other}")),
+        }
+    }
+}
+
+/// The version control systems diffding can count changes in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Vcs {
+    #[default]
+    Git,
+    Jujutsu,
+    Mercurial,
+}
+
+impl Vcs {
+    /// The directory that marks a repository's root.
+    fn marker(&self) -> &'static str {
+        match self {
+            Vcs::Git => ".git",
+            Vcs::Jujutsu => ".jj",
+            Vcs::Mercurial => ".hg",
+        }
+    }
+
+    /// How the revision the changes are counted from is written.
+    pub fn revision_label(&self) -> &'static str {
+        match self {
+            Vcs::Git => "HEAD",
+            // The working copy is itself a change in jj
+            Vcs::Jujutsu => "@",
+            Vcs::Mercurial => ".",
+        }
+    }
+
+    /// What the VCS calls a revision.
+    pub fn revision_noun(&self) -> &'static str {
+        match self {
+            Vcs::Git => "Commit",
+            Vcs::Jujutsu => "Change",
+            Vcs::Mercurial => "Changeset",
+        }
+    }
+
+    /// What the VCS calls a branch.
+    pub fn branch_noun(&self) -> &'static str {
+        match self {
+            Vcs::Git | Vcs::Mercurial => "Branch",
+            Vcs::Jujutsu => "Bookmark",
+        }
+    }
+
+    /// Shown when the working copy isn't on a branch.
+    pub fn no_branch_label(&self) -> &'static str {
+        match self {
+            Vcs::Git | Vcs::Mercurial => "detached HEAD",
+            Vcs::Jujutsu => "no bookmark",
+        }
+    }
+}
+
+/// Finds which VCS the repository containing `path` uses, looking upward
+/// from it. jj repositories colocated with git have both `.jj` and `.git`
+/// and count as jj.
+pub fn detect_vcs(path: &Path) -> Option<Vcs> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    path.ancestors().find_map(|dir| {
+        [Vcs::Jujutsu, Vcs::Mercurial, Vcs::Git]
+            .into_iter()
+            .find(|vcs| dir.join(vcs.marker()).exists())
+    })
+}
+
+/// Opens the repository containing `path`. jj and Mercurial repositories
+/// get their own backends; for git, `kind` picks one, falling back to the
+/// git CLI when the repository can't be opened natively. Fails when
+/// there's no repository.
+pub fn open_backend(kind: BackendKind, path: &Path) -> GitResult<Box<dyn GitBackend>> {
+    match detect_vcs(path) {
+        Some(Vcs::Jujutsu) => return Ok(Box::new(JjBackend::open(path)?)),
+        Some(Vcs::Mercurial) => return Ok(Box::new(HgBackend::open(path)?)),
+        Some(Vcs::Git) | None => {}
+    }
+    match kind {
+        BackendKind::Native => match NativeBackend::open(path) {
+            Ok(backend) => Ok(Box::new(backend)),
+            Err(_) => Ok(Box::new(CliBackend::open(path)?)),
+        },
+        BackendKind::Cli => Ok(Box::new(CliBackend::open(path)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn the_nearest_marker_decides_the_vcs() {
+        let root = std::env::temp_dir().join(format!("diffding-vcs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let nested = root.join("hg/src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("hg/.hg")).unwrap();
+
+        assert_eq!(detect_vcs(&root), Some(Vcs::Git));
+        assert_eq!(detect_vcs(&nested), Some(Vcs::Mercurial));
+
+        // Colocated with git
+        fs::create_dir_all(root.join(".jj")).unwrap();
+        assert_eq!(detect_vcs(&root), Some(Vcs::Jujutsu));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
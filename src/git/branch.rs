@@ -0,0 +1,34 @@
+/// Where HEAD points, from local refs only; nothing is fetched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BranchState {
+    /// The checked-out branch, or `None` when HEAD is detached
+    pub name: Option<String>,
+    /// The branch's upstream, when it has one
+    pub upstream: Option<Upstream>,
+}
+
+impl BranchState {
+    pub fn is_detached(&self) -> bool {
+        self.name.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Upstream {
+    /// Such as `origin/main`
+    pub name: String,
+    /// Commits on the branch that aren't on the upstream
+    pub ahead: usize,
+    /// Commits on the upstream that aren't on the branch
+    pub behind: usize,
+    /// Commit time, in seconds since the epoch, of the oldest unpushed commit
+    pub oldest_unpushed: Option<i64>,
+}
+
+/// Where HEAD forked from the base branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeBase {
+    pub commit: String,
+    /// Commit time, in seconds since the epoch
+    pub time: i64,
+}
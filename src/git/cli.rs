@@ -0,0 +1,588 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use crate::git::{
+    untracked_file_change, BranchState, CommandOutcome, DiffTarget, FileChange, FilePatch,
+    FileStatus, GitBackend, GitResult, MergeBase, RepoStatus, Upstream,
+};
+
+/// Runs `git` for every query. Slower than the native backend, but works
+/// anywhere git is installed.
+#[derive(Debug)]
+pub struct CliBackend {
+    /// Where git starts looking for the repository
+    dir: PathBuf,
+}
+
+impl CliBackend {
+    /// Fails unless `dir` is inside a repository.
+    pub fn open(dir: &Path) -> GitResult<Self> {
+        let backend = Self {
+            dir: dir.to_path_buf(),
+        };
+        backend.git(&["rev-parse", "--git-dir"])?;
+        Ok(backend)
+    }
+
+    fn git(&self, args: &[&str]) -> GitResult<String> {
+        let output = git_command(&self.dir).args(args).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(stderr.trim().into());
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// The files `git diff <target_args>` lists, with their counts.
+    fn diff(&self, target_args: &[&str], ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        let mut args = vec![
+            "diff",
+            "--no-ext-diff",
+            "--no-color",
+            "--raw",
+            "--numstat",
+            "-z",
+        ];
+        if ignore_whitespace {
+            args.extend_from_slice(&["-w", "--ignore-blank-lines"]);
+        }
+        args.extend_from_slice(target_args);
+
+        parse_raw_numstat(&self.git(&args)?)
+    }
+
+    fn git_with_input(&self, args: &[&str], input: &[u8]) -> GitResult<String> {
+        let mut child = git_command(&self.dir)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .ok_or("failed to open stdin")?
+            .write_all(input)?;
+        let output = child.wait_with_output()?;
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+/// A `git` command run in `dir`, with output that doesn't depend on the
+/// user's locale.
+pub fn git_command(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(dir)
+        .env("LC_ALL", "C")
+        .env("LANGUAGE", "C");
+    command
+}
+
+impl GitBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    fn workdir(&self) -> GitResult<PathBuf> {
+        Ok(PathBuf::from(
+            self.git(&["rev-parse", "--show-toplevel"])?.trim(),
+        ))
+    }
+
+    fn git_dir(&self) -> GitResult<PathBuf> {
+        Ok(PathBuf::from(
+            self.git(&["rev-parse", "--absolute-git-dir"])?.trim(),
+        ))
+    }
+
+    fn repo_status(&self) -> GitResult<RepoStatus> {
+        let Ok(is_bare) = self.git(&["rev-parse", "--is-bare-repository"]) else {
+            return Ok(RepoStatus::NotARepository);
+        };
+        if is_bare.trim() == "true" {
+            return Ok(RepoStatus::Bare);
+        }
+        if self
+            .git(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .is_err()
+        {
+            return Ok(RepoStatus::UnbornBranch);
+        }
+        Ok(RepoStatus::Ready)
+    }
+
+    fn current_commit(&self) -> GitResult<String> {
+        Ok(self.git(&["rev-parse", "HEAD"])?.trim().to_string())
+    }
+
+    fn current_commit_short(&self) -> GitResult<String> {
+        Ok(self
+            .git(&["rev-parse", "--short", "HEAD"])?
+            .trim()
+            .to_string())
+    }
+
+    fn head_commit_time(&self) -> GitResult<i64> {
+        Ok(self
+            .git(&["log", "-1", "--format=%ct", "HEAD"])?
+            .trim()
+            .parse()?)
+    }
+
+    fn branch(&self) -> GitResult<BranchState> {
+        let Ok(name) = self.git(&["symbolic-ref", "--short", "--quiet", "HEAD"]) else {
+            return Ok(BranchState::default());
+        };
+        let Ok(upstream_name) = self.git(&[
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ]) else {
+            return Ok(BranchState {
+                name: Some(name.trim().to_string()),
+                upstream: None,
+            });
+        };
+
+        let counts = self.git(&["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])?;
+        let (ahead, behind) = counts
+            .trim()
+            .split_once('\t')
+            .ok_or("malformed rev-list count")?;
+        let oldest_unpushed = self
+            .git(&["log", "--format=%ct", "@{upstream}..HEAD"])?
+            .lines()
+            .filter_map(|time| time.parse::<i64>().ok())
+            .min();
+
+        Ok(BranchState {
+            name: Some(name.trim().to_string()),
+            upstream: Some(Upstream {
+                name: upstream_name.trim().to_string(),
+                ahead: ahead.parse()?,
+                behind: behind.parse()?,
+                oldest_unpushed,
+            }),
+        })
+    }
+
+    fn merge_base(&self, base: &str) -> GitResult<Option<MergeBase>> {
+        let Ok(commit) = self.git(&["merge-base", "HEAD", base]) else {
+            return Ok(None);
+        };
+        let commit = commit.trim().to_string();
+        let time = self
+            .git(&["log", "-1", "--format=%ct", &commit])?
+            .trim()
+            .parse()?;
+        Ok(Some(MergeBase { commit, time }))
+    }
+
+    fn changes_since(&self, commit: &str, ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        self.diff(&[commit], ignore_whitespace)
+    }
+
+    fn file_changes(
+        &self,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> GitResult<Vec<FileChange>> {
+        let target_args: &[&str] = match target {
+            DiffTarget::Staged => &["--cached"],
+            DiffTarget::Unstaged => &[],
+            DiffTarget::Combined => &["HEAD"],
+        };
+        self.diff(target_args, ignore_whitespace)
+    }
+
+    fn untracked_files(&self, max_size: u64) -> GitResult<Vec<FileChange>> {
+        let workdir = self.workdir()?;
+        let stdout = self.git(&[
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "--full-name",
+            "-z",
+        ])?;
+        let files = stdout
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .filter_map(|path| untracked_file_change(Path::new(path), &workdir, max_size))
+            .collect();
+
+        Ok(files)
+    }
+
+    fn generated_by_attributes(&self, paths: &[PathBuf]) -> GitResult<HashSet<PathBuf>> {
+        let workdir = self.workdir()?;
+        let mut input = Vec::new();
+        for path in paths {
+            input.extend_from_slice(workdir.join(path).to_string_lossy().as_bytes());
+            input.push(0);
+        }
+
+        let stdout = self.git_with_input(
+            &["check-attr", "-z", "--stdin", "linguist-generated", "diff"],
+            &input,
+        )?;
+        Ok(parse_check_attr(&stdout, &workdir))
+    }
+
+    fn submodules(&self) -> GitResult<Vec<PathBuf>> {
+        let stdout = self.git(&["ls-files", "--stage", "-z"])?;
+        Ok(parse_gitlinks(&stdout))
+    }
+
+    fn open_submodule(&self, path: &Path) -> GitResult<Box<dyn GitBackend>> {
+        let dir = self.workdir()?.join(path);
+        let submodule = CliBackend::open(&dir)?;
+        // git looks upward, so an empty submodule directory finds the superproject
+        if submodule.workdir()?.canonicalize()? != dir.canonicalize()? {
+            return Err(format!("submodule not checked out: {}", path.display()).into());
+        }
+        Ok(Box::new(submodule))
+    }
+
+    fn commit(&self, message: &str, all: bool) -> GitResult<CommandOutcome> {
+        commit(&self.dir, message, all)
+    }
+
+    fn stage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        stage(&self.workdir()?, path)
+    }
+
+    fn unstage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        unstage(&self.workdir()?, path)
+    }
+
+    fn file_patch(&self, path: &Path, staged: bool) -> GitResult<FilePatch> {
+        file_patch(&self.workdir()?, path, staged)
+    }
+
+    fn apply_hunk(
+        &self,
+        patch: &FilePatch,
+        hunk: usize,
+        staged: bool,
+    ) -> GitResult<CommandOutcome> {
+        apply_hunk(&self.workdir()?, patch, hunk, staged)
+    }
+}
+
+/// Commits in `dir` through the git command, so hooks and signing behave
+/// exactly as they do in the terminal. Shared with the native backend.
+pub fn commit(dir: &Path, message: &str, all: bool) -> GitResult<CommandOutcome> {
+    if all {
+        let outcome = run(git_command(dir).args(["add", "--all"]), None)?;
+        if !outcome.success {
+            return Ok(outcome);
+        }
+    }
+    run(
+        git_command(dir).args(["commit", "--file=-"]),
+        Some(message.as_bytes()),
+    )
+}
+
+/// Stages everything about `path`, deletion included.
+pub fn stage(dir: &Path, path: &Path) -> GitResult<CommandOutcome> {
+    let mut command = git_command(dir);
+    command
+        .args(["--literal-pathspecs", "add", "--all", "--"])
+        .arg(path);
+    run(&mut command, None)
+}
+
+pub fn unstage(dir: &Path, path: &Path) -> GitResult<CommandOutcome> {
+    let mut command = git_command(dir);
+    command
+        .args(["--literal-pathspecs", "reset", "--quiet", "--"])
+        .arg(path);
+    run(&mut command, None)
+}
+
+/// `path`'s diff against the index, or the index's against HEAD when
+/// `staged` is set. Prefixes are fixed so `git apply` can read it back
+/// whatever the user's diff settings. A diff that isn't valid UTF-8 can't
+/// be split into hunks without changing it, so it's an error.
+pub fn file_patch(dir: &Path, path: &Path, staged: bool) -> GitResult<FilePatch> {
+    let mut command = git_command(dir);
+    command.args([
+        "--literal-pathspecs",
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--src-prefix=a/",
+        "--dst-prefix=b/",
+    ]);
+    if staged {
+        command.arg("--cached");
+    }
+    let output = command.arg("--").arg(path).output()?;
+    if !output.status.success() {
+        return Err(command_output(&output).into());
+    }
+    let diff = String::from_utf8(output.stdout)
+        .map_err(|_| "the diff isn't valid UTF-8; stage the whole file instead")?;
+    Ok(FilePatch::parse(&diff))
+}
+
+/// Applies one hunk to the index: stages it, or unstages it when the
+/// patch came from the index (`staged`).
+pub fn apply_hunk(
+    dir: &Path,
+    patch: &FilePatch,
+    hunk: usize,
+    staged: bool,
+) -> GitResult<CommandOutcome> {
+    let hunk_patch = patch.hunk_patch(hunk).ok_or("no such hunk")?;
+    let mut command = git_command(dir);
+    command.args(["apply", "--cached"]);
+    if staged {
+        command.arg("--reverse");
+    }
+    run(command.arg("-"), Some(hunk_patch.as_bytes()))
+}
+
+/// Runs a command that changes the repository, feeding it `input`. A
+/// non-zero exit is an unsuccessful outcome rather than an error.
+pub fn run(command: &mut Command, input: Option<&[u8]>) -> GitResult<CommandOutcome> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .ok_or("failed to open stdin")?
+            .write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    Ok(CommandOutcome {
+        success: output.status.success(),
+        output: command_output(&output),
+    })
+}
+
+/// stdout followed by stderr, trimmed.
+pub fn command_output(output: &Output) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    format!("{}\n{}", stdout.trim(), stderr.trim())
+        .trim()
+        .to_string()
+}
+
+/// Parses `git check-attr -z` output, which is a flat list of
+/// `path NUL attribute NUL value NUL` triples.
+fn parse_check_attr(stdout: &str, workdir: &Path) -> HashSet<PathBuf> {
+    let fields: Vec<&str> = stdout.split('\0').collect();
+    fields
+        .chunks_exact(3)
+        .filter(|triple| {
+            matches!(
+                (triple[1], triple[2]),
+                ("linguist-generated", "set" | "true") | ("diff", "unset")
+            )
+        })
+        .map(|triple| {
+            let path = Path::new(triple[0]);
+            path.strip_prefix(workdir).unwrap_or(path).to_path_buf()
+        })
+        .collect()
+}
+
+/// Picks the submodules out of `git ls-files --stage -z`, whose entries
+/// are `<mode> <object> <stage> TAB <path>`; submodules have mode 160000.
+fn parse_gitlinks(stdout: &str) -> Vec<PathBuf> {
+    stdout
+        .split('\0')
+        .filter_map(|entry| entry.split_once('\t'))
+        .filter(|(meta, _)| meta.starts_with("160000 "))
+        .map(|(_, path)| PathBuf::from(path))
+        .collect()
+}
+
+/// Parses `git diff --raw --numstat -z`.
+///
+/// The output lists every file as a `--raw` record, followed by a `--numstat`
+/// record for each file with countable changes. Files missing from the
+/// numstat half (all changes ignored by `-w`, for example) get zero counts.
+///
+/// Raw records are `:<modes and hashes> <status>` NUL `<path>` NUL, with an
+/// extra NUL-terminated source path before the destination for renames and
+/// copies. Numstat records are `<insertions> TAB <deletions> TAB <path>` NUL,
+/// or for renames an empty path followed by the two paths, each NUL-terminated.
+/// Binary files have `-` for both counts.
+pub fn parse_raw_numstat(stdout: &str) -> GitResult<Vec<FileChange>> {
+    let mut fields = stdout.split('\0').filter(|field| !field.is_empty());
+    let mut files: Vec<FileChange> = Vec::new();
+    let mut counts: HashMap<String, (i32, i32, bool)> = HashMap::new();
+
+    while let Some(field) = fields.next() {
+        if let Some(raw) = field.strip_prefix(':') {
+            let code = raw.rsplit(' ').next().unwrap_or_default();
+            let status = code
+                .chars()
+                .next()
+                .and_then(FileStatus::from_code)
+                .ok_or_else(|| format!("unknown status: {code}"))?;
+            if matches!(status, FileStatus::Renamed | FileStatus::Copied) {
+                fields.next().ok_or("missing source path")?;
+            }
+            let path = fields.next().ok_or("missing path")?;
+            // Unmerged paths get a `U` record and a record for the worktree
+            // side; keep one entry, marked unmerged
+            match files.iter_mut().find(|file| file.path == Path::new(path)) {
+                Some(file) if status == FileStatus::Unmerged => file.status = status,
+                Some(_) => {}
+                None => files.push(FileChange::new(path, 0, 0, status)),
+            }
+            continue;
+        }
+
+        let mut numstat = field.splitn(3, '\t');
+        let insertions = numstat.next().unwrap_or_default();
+        let deletions = numstat.next().ok_or("malformed numstat record")?;
+        let path = match numstat.next() {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                fields.next().ok_or("missing source path")?;
+                fields.next().ok_or("missing path")?
+            }
+        };
+        let binary = insertions == "-" && deletions == "-";
+        counts.insert(
+            path.to_string(),
+            (
+                insertions.parse().unwrap_or(0),
+                deletions.parse().unwrap_or(0),
+                binary,
+            ),
+        );
+    }
+
+    for file in files.iter_mut() {
+        if let Some((insertions, deletions, binary)) =
+            file.path.to_str().and_then(|path| counts.get(path))
+        {
+            file.insertions = *insertions;
+            file.deletions = *deletions;
+            file.binary = *binary;
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASHES: &str = "0000000 1111111";
+
+    fn raw(status: &str) -> String {
+        format!(":100644 100644 {HASHES} {status}")
+    }
+
+    #[test]
+    fn empty_diff() {
+        assert_eq!(parse_raw_numstat("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn insert_only_diff() {
+        let stdout = format!("{}\0src/new.rs\012\t0\tsrc/new.rs\0", raw("A"));
+
+        assert_eq!(
+            parse_raw_numstat(&stdout).unwrap(),
+            vec![FileChange::new("src/new.rs", 12, 0, FileStatus::Added)]
+        );
+    }
+
+    #[test]
+    fn delete_only_diff() {
+        let stdout = format!("{}\0src/old.rs\00\t5\tsrc/old.rs\0", raw("M"));
+
+        assert_eq!(
+            parse_raw_numstat(&stdout).unwrap(),
+            vec![FileChange::new("src/old.rs", 0, 5, FileStatus::Modified)]
+        );
+    }
+
+    #[test]
+    fn binary_diff() {
+        let stdout = format!("{}\0logo.png\0-\t-\tlogo.png\0", raw("M"));
+
+        let mut expected = FileChange::new("logo.png", 0, 0, FileStatus::Modified);
+        expected.binary = true;
+        assert_eq!(parse_raw_numstat(&stdout).unwrap(), vec![expected]);
+    }
+
+    #[test]
+    fn renames_and_unusual_paths() {
+        let stdout = format!(
+            "{}\0old name.rs\0new\tname.rs\0{}\0odd\npath.txt\0\
+             3\t1\t\0old name.rs\0new\tname.rs\0\
+             2\t0\todd\npath.txt\0",
+            raw("R087"),
+            raw("M"),
+        );
+
+        assert_eq!(
+            parse_raw_numstat(&stdout).unwrap(),
+            vec![
+                FileChange::new("new\tname.rs", 3, 1, FileStatus::Renamed),
+                FileChange::new("odd\npath.txt", 2, 0, FileStatus::Modified),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_without_countable_changes_get_zero_counts() {
+        let stdout = format!(
+            "{}\0reformatted.rs\0{}\0changed.rs\04\t2\tchanged.rs\0",
+            raw("M"),
+            raw("M"),
+        );
+
+        assert_eq!(
+            parse_raw_numstat(&stdout).unwrap(),
+            vec![
+                FileChange::new("reformatted.rs", 0, 0, FileStatus::Modified),
+                FileChange::new("changed.rs", 4, 2, FileStatus::Modified),
+            ]
+        );
+    }
+
+    #[test]
+    fn gitlinks_are_submodules() {
+        let stdout = "100644 1111111 0\tsrc/main.rs\x00160000 2222222 0\tlibs/core\0";
+
+        assert_eq!(parse_gitlinks(stdout), vec![PathBuf::from("libs/core")]);
+    }
+
+    #[test]
+    fn unmerged_paths_are_listed_once() {
+        let stdout = format!(
+            ":000000 100644 0000000 0000000 U\0f.rs\0{}\0f.rs\00\t0\tf.rs\04\t0\tf.rs\0",
+            raw("M"),
+        );
+
+        assert_eq!(
+            parse_raw_numstat(&stdout).unwrap(),
+            vec![FileChange::new("f.rs", 4, 0, FileStatus::Unmerged)]
+        );
+    }
+}
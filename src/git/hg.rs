@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::git::cli::{command_output, run};
+use crate::git::stat::{parse_diff_stat, parse_statuses};
+use crate::git::{
+    untracked_file_change, BranchState, CommandOutcome, DiffTarget, FileChange, FilePatch,
+    FileStatus, GitBackend, GitResult, MergeBase, RepoStatus, Vcs,
+};
+
+/// Runs `hg` for every query.
+///
+/// Mercurial has no index, so the working directory's diff against its
+/// parent, `.`, counts as both the unstaged and the combined changes, and
+/// nothing is ever staged.
+#[derive(Debug)]
+pub struct HgBackend {
+    root: PathBuf,
+}
+
+impl HgBackend {
+    /// Fails unless `dir` is inside a Mercurial repository.
+    pub fn open(dir: &Path) -> GitResult<Self> {
+        let output = hg_command(dir).arg("root").output()?;
+        if !output.status.success() {
+            return Err(command_output(&output).into());
+        }
+        Ok(Self {
+            root: PathBuf::from(String::from_utf8(output.stdout)?.trim()),
+        })
+    }
+
+    fn hg(&self, args: &[&str]) -> GitResult<String> {
+        let output = hg_command(&self.root).args(args).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(stderr.trim().into());
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// The files `hg diff <rev_args>` lists, with their counts.
+    fn diff(&self, rev_args: &[&str], ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        let mut status_args = vec!["status", "--modified", "--added", "--removed", "--deleted"];
+        status_args.extend_from_slice(rev_args);
+        // `R` is a removed file here, and `!` one deleted without `hg remove`
+        let statuses = parse_statuses(&self.hg(&status_args)?, |code| match code {
+            'A' => Some(FileStatus::Added),
+            'M' => Some(FileStatus::Modified),
+            'R' | '!' => Some(FileStatus::Deleted),
+            _ => None,
+        });
+
+        let mut stat_args = vec!["diff", "--stat"];
+        if ignore_whitespace {
+            stat_args.extend_from_slice(&["--ignore-all-space", "--ignore-blank-lines"]);
+        }
+        stat_args.extend_from_slice(rev_args);
+        Ok(parse_diff_stat(&self.hg(&stat_args)?, &statuses))
+    }
+
+    /// `template` rendered for the working directory's parent.
+    fn parent(&self, template: &str) -> GitResult<String> {
+        Ok(self
+            .hg(&["log", "-r", ".", "-T", template])?
+            .trim()
+            .to_string())
+    }
+}
+
+/// An `hg` command run at `dir`. `HGPLAIN` keeps the user's configuration
+/// from changing the output, and `COLUMNS` keeps `--stat` graphs from being
+/// scaled down to fit a terminal.
+fn hg_command(dir: &Path) -> Command {
+    let mut command = Command::new("hg");
+    command
+        .arg("--cwd")
+        .arg(dir)
+        .env("HGPLAIN", "1")
+        .env("COLUMNS", "1000")
+        .env("LC_ALL", "C");
+    command
+}
+
+/// The common ancestor of `.` and `base`. The name is quoted, so `-`, `/`
+/// and `.` in a branch like `release-1.2` aren't read as operators.
+fn ancestor_revset(base: &str) -> String {
+    format!("ancestor(., {base:?})")
+}
+
+impl GitBackend for HgBackend {
+    fn name(&self) -> &'static str {
+        "hg"
+    }
+
+    fn vcs(&self) -> Vcs {
+        Vcs::Mercurial
+    }
+
+    fn workdir(&self) -> GitResult<PathBuf> {
+        Ok(self.root.clone())
+    }
+
+    fn git_dir(&self) -> GitResult<PathBuf> {
+        Ok(self.root.join(".hg"))
+    }
+
+    fn repo_status(&self) -> GitResult<RepoStatus> {
+        if !self.root.join(".hg").exists() {
+            return Ok(RepoStatus::NotARepository);
+        }
+        // The null revision, before anything is committed
+        if self.parent("{rev}")? == "-1" {
+            return Ok(RepoStatus::UnbornBranch);
+        }
+        Ok(RepoStatus::Ready)
+    }
+
+    fn current_commit(&self) -> GitResult<String> {
+        self.parent("{node}")
+    }
+
+    fn current_commit_short(&self) -> GitResult<String> {
+        self.parent("{node|short}")
+    }
+
+    fn head_commit_time(&self) -> GitResult<i64> {
+        // `hgdate` is the timestamp followed by the timezone offset
+        let date = self.parent("{date|hgdate}")?;
+        let timestamp = date.split_whitespace().next().ok_or("missing date")?;
+        Ok(timestamp.parse()?)
+    }
+
+    /// The named branch. Outgoing changesets need the remote, so there's
+    /// never an upstream.
+    fn branch(&self) -> GitResult<BranchState> {
+        Ok(BranchState {
+            name: Some(self.hg(&["branch"])?.trim().to_string()),
+            upstream: None,
+        })
+    }
+
+    /// The common ancestor of the working directory's parent and `base`,
+    /// a branch, bookmark or other revision.
+    fn merge_base(&self, base: &str) -> GitResult<Option<MergeBase>> {
+        let revset = ancestor_revset(base);
+        let Ok(ancestor) = self.hg(&["log", "-r", &revset, "-T", "{node} {date|hgdate}"]) else {
+            return Ok(None);
+        };
+        let mut fields = ancestor.split_whitespace();
+        let (Some(commit), Some(time)) = (fields.next(), fields.next()) else {
+            return Ok(None);
+        };
+        Ok(Some(MergeBase {
+            commit: commit.to_string(),
+            time: time.parse()?,
+        }))
+    }
+
+    fn changes_since(&self, commit: &str, ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        self.diff(&["--rev", commit], ignore_whitespace)
+    }
+
+    fn file_changes(
+        &self,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> GitResult<Vec<FileChange>> {
+        if target == DiffTarget::Staged {
+            return Ok(Vec::new());
+        }
+        self.diff(&[], ignore_whitespace)
+    }
+
+    fn untracked_files(&self, max_size: u64) -> GitResult<Vec<FileChange>> {
+        let stdout = self.hg(&["status", "--unknown", "--no-status", "--print0"])?;
+        let files = stdout
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .filter_map(|path| untracked_file_change(Path::new(path), &self.root, max_size))
+            .collect();
+
+        Ok(files)
+    }
+
+    fn generated_by_attributes(&self, _paths: &[PathBuf]) -> GitResult<HashSet<PathBuf>> {
+        Ok(HashSet::new())
+    }
+
+    fn submodules(&self) -> GitResult<Vec<PathBuf>> {
+        Ok(Vec::new())
+    }
+
+    fn open_submodule(&self, path: &Path) -> GitResult<Box<dyn GitBackend>> {
+        Err(format!("subrepositories aren't supported: {}", path.display()).into())
+    }
+
+    /// Runs `hg commit`, adding untracked files and removing missing ones
+    /// first when `all` is set.
+    fn commit(&self, message: &str, all: bool) -> GitResult<CommandOutcome> {
+        let mut command = hg_command(&self.root);
+        command.args(["commit", "--logfile", "-"]);
+        if all {
+            command.arg("--addremove");
+        }
+        run(&mut command, Some(message.as_bytes()))
+    }
+
+    fn stage(&self, _path: &Path) -> GitResult<CommandOutcome> {
+        Ok(CommandOutcome::failed("Mercurial has no staging area"))
+    }
+
+    fn unstage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        self.stage(path)
+    }
+
+    fn file_patch(&self, path: &Path, staged: bool) -> GitResult<FilePatch> {
+        if staged {
+            return Ok(FilePatch::default());
+        }
+        let pattern = format!("path:{}", path.to_string_lossy());
+        Ok(FilePatch::parse(&self.hg(&["diff", "--git", &pattern])?))
+    }
+
+    fn apply_hunk(
+        &self,
+        _patch: &FilePatch,
+        _hunk: usize,
+        _staged: bool,
+    ) -> GitResult<CommandOutcome> {
+        Ok(CommandOutcome::failed("Mercurial has no staging area"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_branch_is_quoted() {
+        assert_eq!(
+            ancestor_revset("release-1.2"),
+            r#"ancestor(., "release-1.2")"#
+        );
+        assert_eq!(
+            ancestor_revset("feature/x.y"),
+            r#"ancestor(., "feature/x.y")"#
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::git::cli::{command_output, git_command};
+use crate::git::GitResult;
+
+/// Marks a pre-commit hook as one diffding wrote, so it can be replaced.
+const HOOK_MARKER: &str = "# Installed by diffding";
+
+/// A hook that was there first is kept under this name and run before
+/// the check.
+const CHAINED_HOOK: &str = "pre-commit.local";
+
+/// What `install` did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Installed {
+    New,
+    /// Replaced a hook diffding had installed before
+    Updated,
+    /// Moved an existing hook to `pre-commit.local`, to be run first
+    Chained,
+}
+
+/// Where the repository at `dir` keeps its hooks, honouring `core.hooksPath`.
+pub fn hooks_dir(dir: &Path) -> GitResult<PathBuf> {
+    let output = git_command(dir)
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()?;
+    if !output.status.success() {
+        return Err(command_output(&output).into());
+    }
+    Ok(dir.join(String::from_utf8(output.stdout)?.trim()))
+}
+
+/// Writes a pre-commit hook to `hooks_dir` that runs `diffding check`,
+/// with `--max` when given. A hook that's already there and isn't
+/// diffding's is kept and run first.
+pub fn install(hooks_dir: &Path, max: Option<i32>) -> GitResult<Installed> {
+    fs::create_dir_all(hooks_dir)?;
+    let hook = hooks_dir.join("pre-commit");
+    let installed = match fs::read_to_string(&hook) {
+        Ok(existing) if existing.contains(HOOK_MARKER) => Installed::Updated,
+        Err(_) if !hook.exists() => Installed::New,
+        _ => {
+            let chained = hooks_dir.join(CHAINED_HOOK);
+            if chained.exists() {
+                return Err(format!(
+                    "both {} and {} exist; merge them by hand",
+                    hook.display(),
+                    chained.display()
+                )
+                .into());
+            }
+            fs::rename(&hook, &chained)?;
+            Installed::Chained
+        }
+    };
+
+    fs::write(&hook, hook_script(max))?;
+    make_executable(&hook)?;
+    Ok(installed)
+}
+
+fn hook_script(max: Option<i32>) -> String {
+    let max = max.map(|max| format!(" --max {max}")).unwrap_or_default();
+    format!(
+        "#!/bin/sh\n\
+         {HOOK_MARKER}: keeps commits under the size limit.\n\
+         # Skip it once with `git commit --no-verify`.\n\
+         chained=\"$(dirname \"$0\")/{CHAINED_HOOK}\"\n\
+         if [ -x \"$chained\" ]; then\n    \
+             \"$chained\" \"$@\" || exit $?\n\
+         fi\n\
+         exec diffding check{max}\n"
+    )
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> GitResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> GitResult<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hooks_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("diffding-hooks-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn installs_and_updates_its_own_hook() {
+        let dir = hooks_dir("own");
+
+        assert_eq!(install(&dir, None).unwrap(), Installed::New);
+        let hook = fs::read_to_string(dir.join("pre-commit")).unwrap();
+        assert!(hook.ends_with("exec diffding check\n"));
+
+        assert_eq!(install(&dir, Some(200)).unwrap(), Installed::Updated);
+        let hook = fs::read_to_string(dir.join("pre-commit")).unwrap();
+        assert!(hook.ends_with("exec diffding check --max 200\n"));
+        assert!(!dir.join(CHAINED_HOOK).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_an_existing_hook_and_runs_it_first() {
+        let dir = hooks_dir("existing");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pre-commit"), "#!/bin/sh\ncargo fmt --check\n").unwrap();
+
+        assert_eq!(install(&dir, None).unwrap(), Installed::Chained);
+        assert_eq!(
+            fs::read_to_string(dir.join(CHAINED_HOOK)).unwrap(),
+            "#!/bin/sh\ncargo fmt --check\n"
+        );
+        assert!(fs::read_to_string(dir.join("pre-commit"))
+            .unwrap()
+            .contains(CHAINED_HOOK));
+
+        // Installing again doesn't touch the chained hook
+        assert_eq!(install(&dir, None).unwrap(), Installed::Updated);
+        assert!(dir.join(CHAINED_HOOK).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
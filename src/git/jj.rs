@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::git::cli::{command_output, run};
+use crate::git::stat::{parse_diff_stat, parse_statuses};
+use crate::git::{
+    BranchState, CommandOutcome, DiffTarget, FileChange, FilePatch, FileStatus, GitBackend,
+    GitResult, MergeBase, RepoStatus, Vcs,
+};
+
+/// Runs `jj` for every query, in standalone repositories and ones colocated
+/// with git alike.
+///
+/// jj has no index: the working copy is itself a change, `@`, which every
+/// edit is snapshotted into. Its diff against its parent counts as both the
+/// unstaged and the combined changes, and nothing is ever staged or untracked.
+#[derive(Debug)]
+pub struct JjBackend {
+    root: PathBuf,
+}
+
+impl JjBackend {
+    /// Fails unless `dir` is inside a jj repository.
+    pub fn open(dir: &Path) -> GitResult<Self> {
+        let output = jj_command(dir).arg("root").output()?;
+        if !output.status.success() {
+            return Err(command_output(&output).into());
+        }
+        Ok(Self {
+            root: PathBuf::from(String::from_utf8(output.stdout)?.trim()),
+        })
+    }
+
+    fn jj(&self, args: &[&str]) -> GitResult<String> {
+        let output = jj_command(&self.root).args(args).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(stderr.trim().into());
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// The files `jj diff <from_args>` lists, with their counts.
+    fn diff(&self, from_args: &[&str], ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        let mut summary_args = vec!["diff", "--summary"];
+        summary_args.extend_from_slice(from_args);
+        let statuses = parse_statuses(&self.jj(&summary_args)?, |code| match code {
+            'A' => Some(FileStatus::Added),
+            'M' => Some(FileStatus::Modified),
+            'D' => Some(FileStatus::Deleted),
+            'R' => Some(FileStatus::Renamed),
+            'C' => Some(FileStatus::Copied),
+            _ => None,
+        });
+
+        let mut stat_args = vec!["diff", "--stat"];
+        if ignore_whitespace {
+            stat_args.push("--ignore-all-space");
+        }
+        stat_args.extend_from_slice(from_args);
+        Ok(parse_diff_stat(&self.jj(&stat_args)?, &statuses))
+    }
+
+    /// `template` rendered for each revision in `revset`.
+    fn log(&self, revset: &str, template: &str) -> GitResult<String> {
+        Ok(self
+            .jj(&["log", "--no-graph", "-r", revset, "-T", template])?
+            .trim()
+            .to_string())
+    }
+}
+
+/// A `jj` command run at `dir`. `COLUMNS` keeps `--stat` graphs from being
+/// scaled down to fit a terminal.
+fn jj_command(dir: &Path) -> Command {
+    let mut command = Command::new("jj");
+    command
+        .current_dir(dir)
+        .args(["--no-pager", "--color=never"])
+        .env("COLUMNS", "1000")
+        .env("LC_ALL", "C");
+    command
+}
+
+/// Where the working copy forked from `base`. The name is quoted, so `-`,
+/// `/` and `.` in a bookmark like `release-1.2` aren't read as operators.
+fn fork_point_revset(base: &str) -> String {
+    format!("fork_point(@ | {base:?})")
+}
+
+impl GitBackend for JjBackend {
+    fn name(&self) -> &'static str {
+        "jj"
+    }
+
+    fn vcs(&self) -> Vcs {
+        Vcs::Jujutsu
+    }
+
+    fn workdir(&self) -> GitResult<PathBuf> {
+        Ok(self.root.clone())
+    }
+
+    fn git_dir(&self) -> GitResult<PathBuf> {
+        Ok(self.root.join(".jj"))
+    }
+
+    fn repo_status(&self) -> GitResult<RepoStatus> {
+        // There's always a working-copy change to count, even before the
+        // first commit
+        if !self.root.join(".jj").exists() {
+            return Ok(RepoStatus::NotARepository);
+        }
+        Ok(RepoStatus::Ready)
+    }
+
+    /// The change ID rather than the commit ID, which changes with every
+    /// snapshot of the working copy.
+    fn current_commit(&self) -> GitResult<String> {
+        self.log("@", "change_id")
+    }
+
+    fn current_commit_short(&self) -> GitResult<String> {
+        self.log("@", "change_id.short(8)")
+    }
+
+    /// When the working copy's parent was committed, or when the working
+    /// copy was started if it sits on the root commit.
+    fn head_commit_time(&self) -> GitResult<i64> {
+        let parent = self.log(
+            "latest(@- ~ root())",
+            r#"committer.timestamp().format("%s")"#,
+        )?;
+        let time = match parent.as_str() {
+            "" => self.log("@", r#"author.timestamp().format("%s")"#)?,
+            _ => parent,
+        };
+        Ok(time.parse()?)
+    }
+
+    /// The first bookmark on the working copy or its parent. Remote
+    /// bookmarks aren't compared, so there's never an upstream.
+    fn branch(&self) -> GitResult<BranchState> {
+        let bookmarks = self.log(
+            "latest((@ | @-) & bookmarks())",
+            r#"local_bookmarks.map(|b| b.name()).join(" ")"#,
+        )?;
+        Ok(BranchState {
+            name: bookmarks.split_whitespace().next().map(str::to_string),
+            upstream: None,
+        })
+    }
+
+    /// Where the working copy forked from `base`, usually a bookmark.
+    fn merge_base(&self, base: &str) -> GitResult<Option<MergeBase>> {
+        let Ok(fork_point) = self.log(
+            &fork_point_revset(base),
+            r#"commit_id ++ " " ++ committer.timestamp().format("%s")"#,
+        ) else {
+            return Ok(None);
+        };
+        let Some((commit, time)) = fork_point.split_once(' ') else {
+            return Ok(None);
+        };
+        Ok(Some(MergeBase {
+            commit: commit.to_string(),
+            time: time.parse()?,
+        }))
+    }
+
+    fn changes_since(&self, commit: &str, ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        self.diff(&["--from", commit], ignore_whitespace)
+    }
+
+    fn file_changes(
+        &self,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> GitResult<Vec<FileChange>> {
+        if target == DiffTarget::Staged {
+            return Ok(Vec::new());
+        }
+        self.diff(&[], ignore_whitespace)
+    }
+
+    fn untracked_files(&self, _max_size: u64) -> GitResult<Vec<FileChange>> {
+        Ok(Vec::new())
+    }
+
+    fn generated_by_attributes(&self, _paths: &[PathBuf]) -> GitResult<HashSet<PathBuf>> {
+        Ok(HashSet::new())
+    }
+
+    fn submodules(&self) -> GitResult<Vec<PathBuf>> {
+        Ok(Vec::new())
+    }
+
+    fn open_submodule(&self, path: &Path) -> GitResult<Box<dyn GitBackend>> {
+        Err(format!("jj doesn't support submodules: {}", path.display()).into())
+    }
+
+    /// Runs `jj commit`, which always takes the whole working copy.
+    fn commit(&self, message: &str, _all: bool) -> GitResult<CommandOutcome> {
+        run(
+            jj_command(&self.root).args(["commit", "--message", message]),
+            None,
+        )
+    }
+
+    fn stage(&self, _path: &Path) -> GitResult<CommandOutcome> {
+        Ok(CommandOutcome::failed("jj has no staging area"))
+    }
+
+    fn unstage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        self.stage(path)
+    }
+
+    fn file_patch(&self, path: &Path, staged: bool) -> GitResult<FilePatch> {
+        if staged {
+            return Ok(FilePatch::default());
+        }
+        let fileset = format!("root-file:{:?}", path.to_string_lossy());
+        Ok(FilePatch::parse(&self.jj(&["diff", "--git", &fileset])?))
+    }
+
+    fn apply_hunk(
+        &self,
+        _patch: &FilePatch,
+        _hunk: usize,
+        _staged: bool,
+    ) -> GitResult<CommandOutcome> {
+        Ok(CommandOutcome::failed("jj has no staging area"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_bookmark_is_quoted() {
+        assert_eq!(
+            fork_point_revset("release-1.2"),
+            r#"fork_point(@ | "release-1.2")"#
+        );
+        assert_eq!(
+            fork_point_revset(r#"odd"name"#),
+            r#"fork_point(@ | "odd\"name")"#
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use crate::filters::PathFilter;
+use crate::git::cli::{command_output, git_command, parse_raw_numstat};
+use crate::git::{FileChange, GitResult};
+
+/// A commit from the log, with the files it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedCommit {
+    pub commit: String,
+    /// Commit time, in seconds since the epoch
+    pub time: i64,
+    /// The first line of the message
+    pub subject: String,
+    pub files: Vec<FileChange>,
+}
+
+impl LoggedCommit {
+    /// The files `filter` doesn't exclude. Generated files can't be told
+    /// apart in old commits, so they count.
+    pub fn counted_files(&self, filter: &PathFilter) -> Vec<FileChange> {
+        self.files
+            .iter()
+            .filter(|file| !filter.is_excluded(&file.path))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Marks where each commit starts, since with `-z` commits and their
+/// files are all NUL-separated.
+const LOG_FORMAT: &str = "--format=%x01%H %ct %s";
+
+/// The configured author's `limit` most recent commits, newest first,
+/// along with the author. Merges are left out, since their changes were
+/// made in other commits.
+pub fn author_commits(dir: &Path, limit: usize) -> GitResult<(String, Vec<LoggedCommit>)> {
+    let author = git(dir, &["config", "user.email"])?.trim().to_string();
+    if author.is_empty() {
+        return Err("user.email isn't set".into());
+    }
+
+    let commits = log(
+        dir,
+        &[
+            "--fixed-strings",
+            &format!("--author=<{author}>"),
+            &format!("--max-count={limit}"),
+        ],
+    )?;
+    Ok((author, commits))
+}
+
+/// The commits in `range`, like `main..HEAD`, oldest first. Merges are
+/// left out, as for `author_commits`.
+pub fn range_commits(dir: &Path, range: &str) -> GitResult<Vec<LoggedCommit>> {
+    log(dir, &["--reverse", "--end-of-options", range, "--"])
+}
+
+fn log(dir: &Path, args: &[&str]) -> GitResult<Vec<LoggedCommit>> {
+    // Keeps the user's log and diff config from changing the output
+    let mut log_args = vec![
+        "log",
+        "-z",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-show-signature",
+        "--no-merges",
+        "--no-renames",
+        "--raw",
+        "--numstat",
+        LOG_FORMAT,
+    ];
+    log_args.extend(args);
+    parse_log(&git(dir, &log_args)?)
+}
+
+fn git(dir: &Path, args: &[&str]) -> GitResult<String> {
+    let output = git_command(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(command_output(&output).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Parses `git log -z --raw --numstat` in `LOG_FORMAT`: for each commit a
+/// `SOH <hash> <time> <subject>` field, then the commit's files as
+/// `git diff --raw --numstat -z` lists them, every field NUL-terminated.
+fn parse_log(stdout: &str) -> GitResult<Vec<LoggedCommit>> {
+    let mut commits: Vec<(LoggedCommit, Vec<&str>)> = Vec::new();
+    for field in stdout.split('\0').filter(|field| !field.is_empty()) {
+        let Some(header) = field.trim_start_matches('\n').strip_prefix('\x01') else {
+            let (_, diff) = commits.last_mut().ok_or("diff before the first commit")?;
+            diff.push(field);
+            continue;
+        };
+        let mut parts = header.splitn(3, ' ');
+        let (Some(commit), Some(time)) = (parts.next(), parts.next()) else {
+            return Err(format!("malformed log header: {header}").into());
+        };
+        let commit = LoggedCommit {
+            commit: commit.to_string(),
+            time: time.parse()?,
+            subject: parts.next().unwrap_or_default().to_string(),
+            files: Vec::new(),
+        };
+        commits.push((commit, Vec::new()));
+    }
+
+    commits
+        .into_iter()
+        .map(|(mut commit, diff)| {
+            let diff = diff.join("\0");
+            commit.files = parse_raw_numstat(diff.trim_start_matches('\n'))?;
+            Ok(commit)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::FileStatus;
+
+    #[test]
+    fn parses_commits_and_their_files() {
+        let stdout = "\x01c0ffee 1700000200 Add a logo\tto the README\0\n\
+                      :000000 100644 0000000 1111111 A\0logo.png\0\
+                      :100644 100644 1111111 2222222 M\0src/lib.rs\0\
+                      -\t-\tlogo.png\0\
+                      12\t3\tsrc/lib.rs\0\
+                      \x01f00d 1700000100\0\n\
+                      :100644 100644 1111111 2222222 M\0README.md\0\
+                      1\t0\tREADME.md\0";
+
+        let commits = parse_log(stdout).unwrap();
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].commit, "c0ffee");
+        assert_eq!(commits[0].time, 1_700_000_200);
+        assert_eq!(commits[0].subject, "Add a logo\tto the README");
+        assert_eq!(commits[0].files[0].status, FileStatus::Added);
+        assert!(commits[0].files[0].binary);
+        assert_eq!(
+            commits[0].files[1],
+            FileChange::new("src/lib.rs", 12, 3, FileStatus::Modified)
+        );
+        assert_eq!(commits[1].subject, "");
+        assert_eq!(
+            commits[1].files,
+            vec![FileChange::new("README.md", 1, 0, FileStatus::Modified)]
+        );
+    }
+
+    #[test]
+    fn paths_are_taken_as_is() {
+        let stdout = "\x01c0ffee 1700000200 Quote\0\n\
+                      :000000 100644 0000000 1111111 A\0docs/sp ace \"q\" \u{e9}.md\0\
+                      2\t0\tdocs/sp ace \"q\" \u{e9}.md\0";
+
+        let commits = parse_log(stdout).unwrap();
+
+        assert_eq!(
+            commits[0].files,
+            vec![FileChange::new(
+                "docs/sp ace \"q\" \u{e9}.md",
+                2,
+                0,
+                FileStatus::Added
+            )]
+        );
+    }
+
+    #[test]
+    fn commits_without_files() {
+        let commits = parse_log("\x01c0ffee 1700000200 Empty\0\x01f00d 1700000100 Also\0").unwrap();
+
+        assert_eq!(commits.len(), 2);
+        assert!(commits.iter().all(|commit| commit.files.is_empty()));
+    }
+
+    #[test]
+    fn empty_log() {
+        assert_eq!(parse_log("").unwrap(), vec![]);
+    }
+}
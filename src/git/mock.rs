@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::git::{
+    BranchState, CommandOutcome, DiffTarget, FileChange, FilePatch, GitBackend, GitResult,
+    MergeBase, RepoStatus,
+};
+
+/// An in-memory repository for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MockBackend {
+    pub commit: String,
+    pub status: RepoStatus,
+    pub branch: BranchState,
+    pub commit_time: i64,
+    pub staged: Vec<FileChange>,
+    pub unstaged: Vec<FileChange>,
+    pub combined: Vec<FileChange>,
+    pub untracked: Vec<FileChange>,
+    pub generated: HashSet<PathBuf>,
+    pub submodules: Vec<(PathBuf, MockBackend)>,
+    /// Where HEAD forked from any base branch
+    pub merge_base: Option<MergeBase>,
+    /// The changes since `merge_base`
+    pub since_merge_base: Vec<FileChange>,
+    /// Makes `merge_base` fail with this message
+    pub merge_base_error: Option<String>,
+}
+
+impl GitBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn workdir(&self) -> GitResult<PathBuf> {
+        Ok(PathBuf::from("/mock-repo"))
+    }
+
+    fn git_dir(&self) -> GitResult<PathBuf> {
+        Ok(PathBuf::from("/mock-repo/.git"))
+    }
+
+    fn repo_status(&self) -> GitResult<RepoStatus> {
+        Ok(self.status)
+    }
+
+    fn current_commit(&self) -> GitResult<String> {
+        Ok(self.commit.clone())
+    }
+
+    fn current_commit_short(&self) -> GitResult<String> {
+        Ok(self.commit.chars().take(7).collect())
+    }
+
+    fn head_commit_time(&self) -> GitResult<i64> {
+        Ok(self.commit_time)
+    }
+
+    fn branch(&self) -> GitResult<BranchState> {
+        Ok(self.branch.clone())
+    }
+
+    fn merge_base(&self, _base: &str) -> GitResult<Option<MergeBase>> {
+        if let Some(error) = &self.merge_base_error {
+            return Err(error.clone().into());
+        }
+        Ok(self.merge_base.clone())
+    }
+
+    fn changes_since(&self, _commit: &str, _ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        Ok(self.since_merge_base.clone())
+    }
+
+    fn file_changes(
+        &self,
+        target: DiffTarget,
+        _ignore_whitespace: bool,
+    ) -> GitResult<Vec<FileChange>> {
+        Ok(match target {
+            DiffTarget::Staged => self.staged.clone(),
+            DiffTarget::Unstaged => self.unstaged.clone(),
+            DiffTarget::Combined => self.combined.clone(),
+        })
+    }
+
+    fn untracked_files(&self, _max_size: u64) -> GitResult<Vec<FileChange>> {
+        Ok(self.untracked.clone())
+    }
+
+    fn generated_by_attributes(&self, paths: &[PathBuf]) -> GitResult<HashSet<PathBuf>> {
+        Ok(paths
+            .iter()
+            .filter(|path| self.generated.contains(*path))
+            .cloned()
+            .collect())
+    }
+
+    fn submodules(&self) -> GitResult<Vec<PathBuf>> {
+        Ok(self
+            .submodules
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect())
+    }
+
+    fn open_submodule(&self, path: &Path) -> GitResult<Box<dyn GitBackend>> {
+        let (_, submodule) = self
+            .submodules
+            .iter()
+            .find(|(submodule_path, _)| submodule_path == path)
+            .ok_or("no such submodule")?;
+        Ok(Box::new(submodule.clone()))
+    }
+
+    fn commit(&self, message: &str, _all: bool) -> GitResult<CommandOutcome> {
+        Ok(CommandOutcome {
+            success: true,
+            output: message.lines().next().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn stage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        Ok(CommandOutcome {
+            success: true,
+            output: path.display().to_string(),
+        })
+    }
+
+    fn unstage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        self.stage(path)
+    }
+
+    fn file_patch(&self, _path: &Path, _staged: bool) -> GitResult<FilePatch> {
+        Ok(FilePatch::default())
+    }
+
+    fn apply_hunk(
+        &self,
+        _patch: &FilePatch,
+        _hunk: usize,
+        _staged: bool,
+    ) -> GitResult<CommandOutcome> {
+        Err("no such hunk".into())
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use git2::{
+    AttrCheckFlags, AttrValue, BranchType, Delta, Diff, DiffOptions, ErrorCode, FileMode, Patch,
+    Repository, Status, StatusOptions, Tree,
+};
+
+use crate::git::cli;
+use crate::git::{
+    untracked_file_change, BranchState, CommandOutcome, DiffTarget, FileChange, FilePatch,
+    FileStatus, GitBackend, GitResult, MergeBase, RepoStatus, Upstream,
+};
+
+/// Reads the repository in-process through libgit2.
+pub struct NativeBackend {
+    repo: Repository,
+}
+
+impl NativeBackend {
+    pub fn open<P: AsRef<Path>>(path: P) -> GitResult<Self> {
+        let repo = Repository::discover(path)?;
+        Ok(Self { repo })
+    }
+
+    fn head_tree(&self) -> Option<Tree<'_>> {
+        self.repo.head().ok()?.peel_to_tree().ok()
+    }
+
+    fn diff(&self, target: DiffTarget, ignore_whitespace: bool) -> GitResult<Diff<'_>> {
+        let head_tree = self.head_tree();
+        let mut diff_options = diff_options(ignore_whitespace);
+
+        let options = Some(&mut diff_options);
+        let diff = match target {
+            DiffTarget::Staged => {
+                self.repo
+                    .diff_tree_to_index(head_tree.as_ref(), None, options)?
+            }
+            DiffTarget::Unstaged => self.repo.diff_index_to_workdir(None, options)?,
+            DiffTarget::Combined => self
+                .repo
+                .diff_tree_to_workdir_with_index(head_tree.as_ref(), options)?,
+        };
+        Ok(diff)
+    }
+}
+
+impl GitBackend for NativeBackend {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn workdir(&self) -> GitResult<PathBuf> {
+        let workdir = self.repo.workdir().ok_or("bare repository")?;
+        Ok(workdir.to_path_buf())
+    }
+
+    fn git_dir(&self) -> GitResult<PathBuf> {
+        Ok(self.repo.path().to_path_buf())
+    }
+
+    fn repo_status(&self) -> GitResult<RepoStatus> {
+        // The repository was deleted after it was opened
+        if !self.repo.path().exists() {
+            return Ok(RepoStatus::NotARepository);
+        }
+        if self.repo.is_bare() {
+            return Ok(RepoStatus::Bare);
+        }
+        match self.repo.head() {
+            Ok(_) => Ok(RepoStatus::Ready),
+            Err(error) if error.code() == ErrorCode::UnbornBranch => Ok(RepoStatus::UnbornBranch),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn current_commit(&self) -> GitResult<String> {
+        let commit = self.repo.head()?.peel_to_commit()?;
+        Ok(commit.id().to_string())
+    }
+
+    fn current_commit_short(&self) -> GitResult<String> {
+        let commit = self.repo.head()?.peel_to_commit()?;
+        let short_id = commit.as_object().short_id()?;
+        Ok(short_id.as_str().unwrap_or_default().to_string())
+    }
+
+    fn head_commit_time(&self) -> GitResult<i64> {
+        Ok(self.repo.head()?.peel_to_commit()?.time().seconds())
+    }
+
+    fn branch(&self) -> GitResult<BranchState> {
+        let head = self.repo.head()?;
+        if !head.is_branch() {
+            return Ok(BranchState::default());
+        }
+        let name = head
+            .shorthand()
+            .ok_or("branch name isn't UTF-8")?
+            .to_string();
+        let head_oid = head.target().ok_or("HEAD has no target")?;
+
+        let upstream = match self.repo.find_branch(&name, BranchType::Local)?.upstream() {
+            Ok(upstream) => {
+                let upstream_name = upstream.name()?.unwrap_or_default().to_string();
+                let upstream_oid = upstream.get().target().ok_or("upstream has no target")?;
+                let (ahead, behind) = self.repo.graph_ahead_behind(head_oid, upstream_oid)?;
+
+                let mut revwalk = self.repo.revwalk()?;
+                revwalk.push(head_oid)?;
+                revwalk.hide(upstream_oid)?;
+                let mut oldest_unpushed = None;
+                for oid in revwalk {
+                    let time = self.repo.find_commit(oid?)?.time().seconds();
+                    oldest_unpushed =
+                        Some(oldest_unpushed.map_or(time, |oldest: i64| oldest.min(time)));
+                }
+
+                Some(Upstream {
+                    name: upstream_name,
+                    ahead,
+                    behind,
+                    oldest_unpushed,
+                })
+            }
+            Err(_) => None,
+        };
+
+        Ok(BranchState {
+            name: Some(name),
+            upstream,
+        })
+    }
+
+    fn merge_base(&self, base: &str) -> GitResult<Option<MergeBase>> {
+        let Ok(base) = self.repo.revparse_single(base) else {
+            return Ok(None);
+        };
+        let head = self.repo.head()?.peel_to_commit()?.id();
+        let Ok(oid) = self.repo.merge_base(head, base.peel_to_commit()?.id()) else {
+            return Ok(None);
+        };
+        Ok(Some(MergeBase {
+            commit: oid.to_string(),
+            time: self.repo.find_commit(oid)?.time().seconds(),
+        }))
+    }
+
+    fn changes_since(&self, commit: &str, ignore_whitespace: bool) -> GitResult<Vec<FileChange>> {
+        let tree = self.repo.revparse_single(commit)?.peel_to_tree()?;
+        let mut diff_options = diff_options(ignore_whitespace);
+        let diff = self
+            .repo
+            .diff_tree_to_workdir_with_index(Some(&tree), Some(&mut diff_options))?;
+        file_changes(diff)
+    }
+
+    fn file_changes(
+        &self,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> GitResult<Vec<FileChange>> {
+        file_changes(self.diff(target, ignore_whitespace)?)
+    }
+
+    fn untracked_files(&self, max_size: u64) -> GitResult<Vec<FileChange>> {
+        let workdir = self.repo.workdir().ok_or("bare repository")?;
+        let mut status_options = StatusOptions::new();
+        status_options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false);
+
+        let statuses = self.repo.statuses(Some(&mut status_options))?;
+        let files = statuses
+            .iter()
+            .filter(|entry| entry.status().contains(Status::WT_NEW))
+            .filter_map(|entry| {
+                let path = entry.path()?;
+                untracked_file_change(Path::new(path), workdir, max_size)
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    fn generated_by_attributes(&self, paths: &[PathBuf]) -> GitResult<HashSet<PathBuf>> {
+        let mut generated = HashSet::new();
+        for path in paths {
+            let attr = |name| {
+                self.repo
+                    .get_attr(path, name, AttrCheckFlags::FILE_THEN_INDEX)
+                    .map(AttrValue::from_string)
+            };
+            let linguist_generated = matches!(
+                attr("linguist-generated")?,
+                AttrValue::True | AttrValue::String("true")
+            );
+            let no_diff = matches!(attr("diff")?, AttrValue::False);
+
+            if linguist_generated || no_diff {
+                generated.insert(path.clone());
+            }
+        }
+
+        Ok(generated)
+    }
+
+    fn submodules(&self) -> GitResult<Vec<PathBuf>> {
+        let submodules = self.repo.submodules()?;
+        Ok(submodules
+            .iter()
+            .map(|submodule| submodule.path().to_path_buf())
+            .collect())
+    }
+
+    fn open_submodule(&self, path: &Path) -> GitResult<Box<dyn GitBackend>> {
+        // `open` rather than `discover`, so an empty submodule directory
+        // doesn't resolve to the superproject
+        let repo = Repository::open(self.workdir()?.join(path))?;
+        Ok(Box::new(Self { repo }))
+    }
+
+    fn commit(&self, message: &str, all: bool) -> GitResult<CommandOutcome> {
+        // libgit2 doesn't run hooks, so commits go through the git command
+        cli::commit(&self.workdir()?, message, all)
+    }
+
+    fn stage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        cli::stage(&self.workdir()?, path)
+    }
+
+    fn unstage(&self, path: &Path) -> GitResult<CommandOutcome> {
+        cli::unstage(&self.workdir()?, path)
+    }
+
+    fn file_patch(&self, path: &Path, staged: bool) -> GitResult<FilePatch> {
+        cli::file_patch(&self.workdir()?, path, staged)
+    }
+
+    fn apply_hunk(
+        &self,
+        patch: &FilePatch,
+        hunk: usize,
+        staged: bool,
+    ) -> GitResult<CommandOutcome> {
+        cli::apply_hunk(&self.workdir()?, patch, hunk, staged)
+    }
+}
+
+fn diff_options(ignore_whitespace: bool) -> DiffOptions {
+    let mut diff_options = DiffOptions::new();
+    diff_options
+        .ignore_whitespace(ignore_whitespace)
+        .ignore_blank_lines(ignore_whitespace);
+    diff_options
+}
+
+/// The files in `diff`, with renames detected.
+fn file_changes(mut diff: Diff<'_>) -> GitResult<Vec<FileChange>> {
+    diff.find_similar(None)?;
+
+    let mut files = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let status = match file_status(delta.status()) {
+            Some(status) => status,
+            None => continue,
+        };
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .ok_or("missing path")?;
+
+        let patch = Patch::from_diff(&diff, index)?;
+        // A submodule with local changes but the same commit shows up as a
+        // `-dirty` line; git's numstat doesn't count it
+        let dirty_submodule = delta.new_file().mode() == FileMode::Commit
+            && delta.old_file().id() == delta.new_file().id();
+        let (insertions, deletions) = match &patch {
+            _ if dirty_submodule => (0, 0),
+            Some(patch) => {
+                let (_, insertions, deletions) = patch.line_stats()?;
+                (insertions as i32, deletions as i32)
+            }
+            None => (0, 0),
+        };
+        let binary = patch.as_ref().map_or(delta.flags().is_binary(), |patch| {
+            patch.delta().flags().is_binary()
+        });
+
+        let mut file_change = FileChange::new(path, insertions, deletions, status);
+        file_change.binary = binary;
+        files.push(file_change);
+    }
+
+    Ok(files)
+}
+
+fn file_status(delta: Delta) -> Option<FileStatus> {
+    match delta {
+        Delta::Added => Some(FileStatus::Added),
+        Delta::Modified => Some(FileStatus::Modified),
+        Delta::Deleted => Some(FileStatus::Deleted),
+        Delta::Renamed => Some(FileStatus::Renamed),
+        Delta::Copied => Some(FileStatus::Copied),
+        Delta::Typechange => Some(FileStatus::TypeChanged),
+        Delta::Conflicted => Some(FileStatus::Unmerged),
+        Delta::Untracked => Some(FileStatus::Untracked),
+        Delta::Unmodified | Delta::Ignored | Delta::Unreadable => None,
+    }
+}
@@ -0,0 +1,132 @@
+/// One file's diff, split into hunks so they can be staged one at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilePatch {
+    /// The `diff --git`, index and `---`/`+++` lines
+    header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A hunk, starting with its `@@` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// Lines added and removed by the hunk.
+    pub fn stats(&self) -> (i32, i32) {
+        self.lines
+            .iter()
+            .skip(1)
+            .fold((0, 0), |(insertions, deletions), line| {
+                match line.as_bytes().first() {
+                    Some(b'+') => (insertions + 1, deletions),
+                    Some(b'-') => (insertions, deletions + 1),
+                    _ => (insertions, deletions),
+                }
+            })
+    }
+}
+
+impl FilePatch {
+    /// Parses the output of `git diff` for a single file. Binary files and
+    /// files with no content changes have no hunks. Lines keep any `\r`, so
+    /// a hunk from a CRLF file still applies.
+    pub fn parse(diff: &str) -> Self {
+        let mut patch = FilePatch::default();
+        for line in diff.split_terminator('\n') {
+            match patch.hunks.last_mut() {
+                _ if line.starts_with("@@") => patch.hunks.push(Hunk {
+                    lines: vec![line.to_string()],
+                }),
+                Some(hunk) => hunk.lines.push(line.to_string()),
+                None => patch.header.push(line.to_string()),
+            }
+        }
+        patch
+    }
+
+    /// A patch holding only hunk `index`, for `git apply`.
+    pub fn hunk_patch(&self, index: usize) -> Option<String> {
+        let hunk = self.hunks.get(index)?;
+        let lines: Vec<&str> = self
+            .header
+            .iter()
+            .chain(&hunk.lines)
+            .map(String::as_str)
+            .collect();
+        Some(lines.join("\n") + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 3b18e51..a5c1966 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn one() {}
+-fn two() {}
++fn deux() {}
+ fn three() {}
+@@ -20,2 +20,4 @@ fn twenty() {}
+ fn twenty_one() {}
++fn twenty_two() {}
++fn twenty_three() {}
+\\ No newline at end of file
+";
+
+    #[test]
+    fn splits_a_diff_into_hunks() {
+        let patch = FilePatch::parse(DIFF);
+
+        assert_eq!(patch.hunks.len(), 2);
+        assert_eq!(patch.hunks[0].stats(), (1, 1));
+        assert_eq!(patch.hunks[1].stats(), (2, 0));
+        assert_eq!(
+            patch.hunk_patch(1).unwrap(),
+            "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 3b18e51..a5c1966 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -20,2 +20,4 @@ fn twenty() {}
+ fn twenty_one() {}
++fn twenty_two() {}
++fn twenty_three() {}
+\\ No newline at end of file
+"
+        );
+    }
+
+    #[test]
+    fn crlf_lines_survive_the_round_trip() {
+        let diff = "\
+diff --git a/notes.txt b/notes.txt
+index 3b18e51..a5c1966 100644
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,2 +1,2 @@
+ one\r
+-two\r
++deux\r
+";
+        let patch = FilePatch::parse(diff);
+
+        assert_eq!(patch.hunks[0].stats(), (1, 1));
+        assert_eq!(patch.hunk_patch(0).unwrap(), diff);
+    }
+
+    #[test]
+    fn binary_files_have_no_hunks() {
+        let patch = FilePatch::parse(
+            "diff --git a/logo.png b/logo.png\nindex 1..2 100644\nBinary files a/logo.png and b/logo.png differ\n",
+        );
+        assert!(patch.hunks.is_empty());
+        assert_eq!(patch.hunk_patch(0), None);
+    }
+}
@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+
+use crate::git::cli::{command_output, git_command, run};
+use crate::git::{CommandOutcome, GitResult};
+
+/// Where snapshots are kept, one ref per snapshot.
+const SNAPSHOT_REFS: &str = "refs/diffding/snapshots/";
+
+/// A copy of a dirty worktree, stored as a stash-like commit so
+/// `git stash apply` can bring it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The last part of the ref, a timestamp like `20240131T174502`
+    pub name: String,
+    pub commit: String,
+    /// When it was taken, in seconds since the epoch
+    pub time: i64,
+}
+
+fn git(dir: &Path, args: &[&str]) -> GitResult<String> {
+    let output = git_command(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(command_output(&output).into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+fn snapshot_ref(name: &str) -> String {
+    format!("{SNAPSHOT_REFS}{name}")
+}
+
+/// Records the worktree, the index and untracked files like
+/// `git stash create --include-untracked` would, without touching any of
+/// them. Returns `None` when the worktree is clean or nothing changed since
+/// the latest snapshot.
+pub fn create(dir: &Path, now: DateTime<Local>) -> GitResult<Option<Snapshot>> {
+    let stash = git(dir, &["stash", "create", "diffding snapshot"])?;
+    let Some(untracked) = untracked_commit(dir)? else {
+        if stash.is_empty() {
+            return Ok(None);
+        }
+        return save(dir, &stash, now);
+    };
+
+    // Rebuilds the stash with the untracked files as a third parent, the
+    // way `git stash --include-untracked` lays it out
+    let (tree, head, index) = if stash.is_empty() {
+        let tree = git(dir, &["rev-parse", "HEAD^{tree}"])?;
+        let index = git(
+            dir,
+            &["commit-tree", &tree, "-p", "HEAD", "-m", "diffding index"],
+        )?;
+        (tree, "HEAD".to_string(), index)
+    } else {
+        (
+            git(dir, &["rev-parse", &format!("{stash}^{{tree}}")])?,
+            format!("{stash}^1"),
+            format!("{stash}^2"),
+        )
+    };
+    let commit = git(
+        dir,
+        &[
+            "commit-tree",
+            &tree,
+            "-p",
+            &head,
+            "-p",
+            &index,
+            "-p",
+            &untracked,
+            "-m",
+            "diffding snapshot",
+        ],
+    )?;
+    save(dir, &commit, now)
+}
+
+/// A parentless commit holding just the untracked, non-ignored files.
+fn untracked_commit(dir: &Path) -> GitResult<Option<String>> {
+    let untracked = git(dir, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    if untracked.is_empty() {
+        return Ok(None);
+    }
+
+    // Builds the tree in a throwaway index so the real one isn't touched
+    let index_file =
+        Path::new(&git(dir, &["rev-parse", "--absolute-git-dir"])?).join("diffding-snapshot-index");
+    let _ = fs::remove_file(&index_file);
+    let tree = write_tree(dir, &index_file, &untracked);
+    let _ = fs::remove_file(&index_file);
+
+    let tree = tree?;
+    let commit = git(
+        dir,
+        &["commit-tree", &tree, "-m", "diffding untracked files"],
+    )?;
+    Ok(Some(commit))
+}
+
+/// Adds the NUL-separated `paths` to the index at `index_file` and writes
+/// it out as a tree.
+fn write_tree(dir: &Path, index_file: &Path, paths: &str) -> GitResult<String> {
+    let mut update_index = git_command(dir);
+    update_index
+        .env("GIT_INDEX_FILE", index_file)
+        .args(["update-index", "--add", "-z", "--stdin"]);
+    let outcome = run(&mut update_index, Some(paths.as_bytes()))?;
+    if !outcome.success {
+        return Err(outcome.output.into());
+    }
+
+    let output = git_command(dir)
+        .env("GIT_INDEX_FILE", index_file)
+        .arg("write-tree")
+        .output()?;
+    if !output.status.success() {
+        return Err(command_output(&output).into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Stores `commit` as a new snapshot, unless it holds the same files as
+/// the latest one.
+fn save(dir: &Path, commit: &str, now: DateTime<Local>) -> GitResult<Option<Snapshot>> {
+    if let Some(latest) = list(dir)?.first() {
+        if contents(dir, &latest.commit)? == contents(dir, commit)? {
+            return Ok(None);
+        }
+    }
+
+    let name = now.format("%Y%m%dT%H%M%S").to_string();
+    // An empty old value makes the update fail rather than overwrite
+    git(dir, &["update-ref", &snapshot_ref(&name), commit, ""])?;
+    Ok(Some(Snapshot {
+        name,
+        commit: git(dir, &["rev-parse", "--short", commit])?,
+        time: now.timestamp(),
+    }))
+}
+
+/// The trees of the worktree, index and untracked files a snapshot holds.
+fn contents(dir: &Path, commit: &str) -> GitResult<String> {
+    let untracked = git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{commit}^3^{{tree}}"),
+        ],
+    )
+    .unwrap_or_default();
+    let trees = git(
+        dir,
+        &[
+            "rev-parse",
+            &format!("{commit}^{{tree}}"),
+            &format!("{commit}^2^{{tree}}"),
+        ],
+    )?;
+    Ok(format!("{trees}\n{untracked}"))
+}
+
+/// Every snapshot, newest first.
+pub fn list(dir: &Path) -> GitResult<Vec<Snapshot>> {
+    let stdout = git(
+        dir,
+        &[
+            "for-each-ref",
+            "--sort=-refname",
+            "--format=%(refname:lstrip=3)%00%(objectname:short)%00%(committerdate:unix)",
+            SNAPSHOT_REFS,
+        ],
+    )?;
+    Ok(parse_snapshots(&stdout))
+}
+
+fn parse_snapshots(stdout: &str) -> Vec<Snapshot> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\0');
+            Some(Snapshot {
+                name: fields.next()?.to_string(),
+                commit: fields.next()?.to_string(),
+                time: fields.next()?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// What the snapshot changed relative to the commit it was taken on,
+/// untracked files included.
+pub fn diff(dir: &Path, name: &str) -> GitResult<String> {
+    let output = git_command(dir)
+        .args([
+            "stash",
+            "show",
+            "--patch",
+            "--include-untracked",
+            "--no-color",
+        ])
+        .arg(snapshot_ref(name))
+        .output()?;
+    if !output.status.success() {
+        return Err(command_output(&output).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Applies the snapshot to the worktree with `git stash apply`, which
+/// refuses rather than overwrite local changes.
+pub fn restore(dir: &Path, name: &str) -> GitResult<CommandOutcome> {
+    let mut command = git_command(dir);
+    command.args(["stash", "apply", &snapshot_ref(name)]);
+    run(&mut command, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_for_each_ref_output() {
+        let stdout =
+            "20240131T174502\x00a1b2c3d\x001706719502\n20240131T164502\x00e4f5a6b\x001706715902";
+
+        assert_eq!(
+            parse_snapshots(stdout),
+            vec![
+                Snapshot {
+                    name: "20240131T174502".to_string(),
+                    commit: "a1b2c3d".to_string(),
+                    time: 1706719502,
+                },
+                Snapshot {
+                    name: "20240131T164502".to_string(),
+                    commit: "e4f5a6b".to_string(),
+                    time: 1706715902,
+                },
+            ]
+        );
+    }
+}
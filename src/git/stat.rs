@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::git::{FileChange, FileStatus};
+
+/// Parses `diff --stat` output as printed by jj and Mercurial, whose lines
+/// are ` <path> | <changed lines> <graph>`, followed by a summary line.
+/// Binary files show `Bin` in place of the count.
+///
+/// The graph is one `+` per insertion and one `-` per deletion unless it
+/// had to be scaled down to fit, in which case the count is split between
+/// them in proportion. Files get the status in `statuses`, or `Modified`.
+pub fn parse_diff_stat(stdout: &str, statuses: &HashMap<PathBuf, FileStatus>) -> Vec<FileChange> {
+    let mut files = Vec::new();
+    for line in stdout.lines() {
+        // The summary line, and anything else that isn't a file
+        let Some((path, stat)) = line.rsplit_once('|') else {
+            continue;
+        };
+        let path = PathBuf::from(renamed_path(path.trim()));
+        let status = statuses.get(&path).copied().unwrap_or(FileStatus::Modified);
+
+        let stat = stat.trim();
+        let (count, graph) = stat.split_once(' ').unwrap_or((stat, ""));
+        let mut file = FileChange::new(path, 0, 0, status);
+        let Ok(count) = count.parse::<i32>() else {
+            file.binary = true;
+            files.push(file);
+            continue;
+        };
+
+        let pluses = graph.chars().filter(|c| *c == '+').count() as i32;
+        let minuses = graph.chars().filter(|c| *c == '-').count() as i32;
+        file.insertions = match pluses + minuses {
+            0 => count,
+            drawn if drawn == count => pluses,
+            drawn => (f64::from(count) * f64::from(pluses) / f64::from(drawn)).round() as i32,
+        };
+        file.deletions = count - file.insertions;
+        files.push(file);
+    }
+    files
+}
+
+/// Parses status lines of a status letter, a space and a path, like
+/// `jj diff --summary` and `hg status`. Each VCS has its own letters, so
+/// `from_code` maps them; lines with other letters are left out.
+pub fn parse_statuses(
+    stdout: &str,
+    from_code: impl Fn(char) -> Option<FileStatus>,
+) -> HashMap<PathBuf, FileStatus> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (code, path) = line.split_once(' ')?;
+            let status = from_code(code.chars().next()?)?;
+            Some((PathBuf::from(renamed_path(path)), status))
+        })
+        .collect()
+}
+
+/// The destination of a renamed path, written `old => new` or
+/// `dir/{old => new}/file`. Other paths are returned unchanged.
+pub fn renamed_path(path: &str) -> String {
+    let Some((before, after)) = path.split_once(" => ") else {
+        return path.to_string();
+    };
+    match (before.rsplit_once('{'), after.split_once('}')) {
+        (Some((prefix, _)), Some((new, suffix))) => {
+            let joined = format!("{prefix}{new}{suffix}");
+            // `dir/{ => sub}/file` leaves a doubled slash when a side is empty
+            joined
+                .replace("//", "/")
+                .trim_start_matches('/')
+                .to_string()
+        }
+        _ => after.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(stdout: &str) -> Vec<FileChange> {
+        parse_diff_stat(stdout, &HashMap::new())
+    }
+
+    #[test]
+    fn empty_stat() {
+        assert_eq!(parse(""), vec![]);
+    }
+
+    #[test]
+    fn counts_come_from_the_graph() {
+        let stdout = " src/lib.rs | 5 +++--\n README.md  | 2 ++\n\
+                      2 files changed, 5 insertions(+), 2 deletions(-)\n";
+
+        assert_eq!(
+            parse(stdout),
+            vec![
+                FileChange::new("src/lib.rs", 3, 2, FileStatus::Modified),
+                FileChange::new("README.md", 2, 0, FileStatus::Modified),
+            ]
+        );
+    }
+
+    #[test]
+    fn scaled_graphs_are_split_in_proportion() {
+        let stdout = format!(" big.rs | 400 {}{}\n", "+".repeat(31), "-".repeat(9));
+
+        assert_eq!(
+            parse(&stdout),
+            vec![FileChange::new("big.rs", 310, 90, FileStatus::Modified)]
+        );
+    }
+
+    #[test]
+    fn binary_files_are_marked() {
+        let mut expected = FileChange::new("logo.png", 0, 0, FileStatus::Added);
+        expected.binary = true;
+        let statuses = HashMap::from([(PathBuf::from("logo.png"), FileStatus::Added)]);
+
+        assert_eq!(
+            parse_diff_stat(" logo.png | Bin\n", &statuses),
+            vec![expected]
+        );
+    }
+
+    #[test]
+    fn statuses_use_the_given_letters() {
+        let stdout = "M src/lib.rs\nR {old => new}.rs\n? notes.txt\n";
+        let from_code = |code| match code {
+            'M' => Some(FileStatus::Modified),
+            'R' => Some(FileStatus::Renamed),
+            _ => None,
+        };
+
+        assert_eq!(
+            parse_statuses(stdout, from_code),
+            HashMap::from([
+                (PathBuf::from("src/lib.rs"), FileStatus::Modified),
+                (PathBuf::from("new.rs"), FileStatus::Renamed),
+            ])
+        );
+    }
+
+    #[test]
+    fn renames_resolve_to_the_new_path() {
+        assert_eq!(renamed_path("old.rs => new.rs"), "new.rs");
+        assert_eq!(renamed_path("src/{old => new}/lib.rs"), "src/new/lib.rs");
+        assert_eq!(renamed_path("src/{ => nested}/lib.rs"), "src/nested/lib.rs");
+        assert_eq!(renamed_path("{src => }/lib.rs"), "lib.rs");
+        assert_eq!(renamed_path("plain.rs"), "plain.rs");
+    }
+}
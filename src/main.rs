@@ -1,4 +1,4 @@
-use std::io::stdout;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crossterm::{terminal::enable_raw_mode, Result};
@@ -6,22 +6,36 @@ use futures::{future::FutureExt, StreamExt};
 use serde_derive::Deserialize;
 use signal_hook::consts::signal::*;
 use signal_hook_tokio::Signals;
-use tokio_util::sync::CancellationToken;
 
 use crate::bell::BellMessage;
-use crate::git::{git_loop, GitState};
+use crate::git::{
+    git_loop, BackendKind, CountMode, GitCommand, GitState, DEFAULT_UNTRACKED_MAX_SIZE,
+};
 use crate::manager::ManagerMessage;
 use crate::ui::UiMessage;
 
+mod adaptive;
+mod audit;
 mod bell;
+mod check;
+mod commands;
+mod commit_dialog;
 mod events;
+mod file_list;
+mod filters;
 mod git;
 mod manager;
+mod operations;
 mod options;
+mod reminders;
+mod scoring;
 mod signals;
+mod snapshots;
+mod staging;
 mod summary;
 mod threshold_gauge;
 mod ui;
+mod watcher;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Options {
@@ -31,6 +45,48 @@ pub struct Options {
     #[allow(dead_code)]
     volume: f32,
     snooze_length: i64,
+    count_mode: CountMode,
+    untracked_max_size: u64,
+    backend: BackendKind,
+    watch: bool,
+    safety_interval: u64,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    skip_generated: bool,
+    insertion_weight: f64,
+    deletion_weight: f64,
+    weights: HashMap<String, f64>,
+    ignore_whitespace: bool,
+    submodules: bool,
+    submodule_include: Vec<String>,
+    submodule_exclude: Vec<String>,
+    max_unpushed_commits: Option<usize>,
+    max_unpushed_hours: Option<f64>,
+    max_dirty_minutes: Option<i64>,
+    base_branch: String,
+    max_branch_size: Option<i32>,
+    max_binary_size: Option<u64>,
+    snapshots: bool,
+    snapshot_interval: Option<i64>,
+    snapshot_on_bell: bool,
+    adaptive_threshold: bool,
+    adaptive_percentile: f64,
+    adaptive_commits: usize,
+    repos: Vec<RepoOptions>,
+    /// `--repo`, which subcommands work on instead of the current directory
+    repo: Option<PathBuf>,
+    /// A subcommand and its arguments; empty to start the TUI
+    subcommand: Vec<String>,
+}
+
+/// A repository to watch and the threshold it's held to.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RepoOptions {
+    /// Where to start looking for the repository
+    path: PathBuf,
+    threshold: i32,
+    /// Whether the entry sets `threshold` itself rather than inheriting it
+    own_threshold: bool,
 }
 
 // TODO: implement bell_ringer and bell
@@ -38,6 +94,11 @@ pub struct Options {
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let options = options::get_options().unwrap();
+    if !options.subcommand.is_empty() {
+        std::process::exit(commands::run(&options));
+    }
+
     let signals = Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT])?;
     let signals_handle = signals.handle();
 
@@ -46,35 +107,36 @@ async fn main() -> Result<()> {
     let tx_bell_signals = tx_bell.clone();
     let signals_task = tokio::spawn(signals::handle_signals(signals, tx_bell_signals));
 
-    let options = options::get_options().unwrap();
-
-    let mut stdout = stdout();
-
-    let kill_token = CancellationToken::new();
-    let child_kill_token = kill_token.child_token();
-
     enable_raw_mode()?;
 
-    let (tx_app, mut rx_app) = tokio::sync::mpsc::channel::<ManagerMessage>(32);
-    let (tx_ui, mut rx_ui) = tokio::sync::mpsc::channel::<UiMessage>(32);
-    let (tx_bell, mut rx_bell) = tokio::sync::mpsc::channel::<BellMessage>(32);
+    let (tx_app, rx_app) = tokio::sync::mpsc::channel::<ManagerMessage>(32);
+    let (tx_ui, rx_ui) = tokio::sync::mpsc::channel::<UiMessage>(32);
 
     let tx_ui_manager = tx_ui.clone();
     let tx_bell_manager = tx_bell.clone();
+    let (tx_git, rx_git): (Vec<_>, Vec<_>) = options
+        .repos
+        .iter()
+        .map(|_| tokio::sync::mpsc::channel::<GitCommand>(8))
+        .unzip();
     let manager = tokio::spawn(manager::manager_loop(
         rx_app,
         tx_ui_manager,
         tx_bell_manager,
+        tx_git,
         options.clone(),
     ));
 
     let tx_app_kb = tx_app.clone();
     let opt_kb = options.clone();
     let kb_handle = tokio::spawn(events::keyboard_events(tx_app_kb, opt_kb));
+    tokio::spawn(events::redraw_ticks(tx_app.clone()));
 
-    let opt_git = options.clone();
-    let tx_app_git = tx_app.clone();
-    let git_handle = tokio::spawn(git_loop(tx_app_git, opt_git));
+    let git_handles: Vec<_> = rx_git
+        .into_iter()
+        .enumerate()
+        .map(|(repo, rx)| tokio::spawn(git_loop(tx_app.clone(), options.clone(), repo, rx)))
+        .collect();
 
     let opt_ui = options.clone();
     let ui_handle = tokio::spawn(ui::ui_loop(rx_ui, opt_ui));
@@ -86,7 +148,9 @@ async fn main() -> Result<()> {
     ui_handle.await.unwrap();
     bell_handle.await.unwrap();
     kb_handle.await.unwrap();
-    git_handle.await.unwrap();
+    for git_handle in git_handles {
+        git_handle.await.unwrap();
+    }
 
     signals_handle.close();
     signals_task.await?;
@@ -1,44 +1,99 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use chrono::{DateTime, Local};
+use crossterm::event::{KeyCode, KeyEvent};
 use crossterm::execute;
 use crossterm::terminal::disable_raw_mode;
 use crossterm::terminal::{Clear, ClearType, LeaveAlternateScreen};
 use tokio::sync::mpsc::{Receiver, Sender};
 
 use crate::bell::BellMessage;
-use crate::{GitState, Options, UiMessage};
+use crate::commit_dialog::{CommitDialog, CommitScope, DialogAction, DialogStatus};
+use crate::git::{CommandOutcome, FilePatch, GitCommand};
+use crate::snapshots::{SnapshotAction, SnapshotPane, SnapshotSettings};
+use crate::staging::{self, StagingAction, StagingPane};
+use crate::{GitState, Options, RepoOptions, UiMessage};
 
 #[derive(Debug)]
 pub enum ManagerMessage {
     Quit,
     Snooze,
-    Git { git_state: GitState },
+    Git {
+        /// Index into `Options::repos`
+        repo: usize,
+        git_state: Box<GitState>,
+    },
+    NextRepo,
     Bell,
     Redraw,
+    /// A key press, interpreted by `key_message`
+    Key(KeyEvent),
+    OpenCommitDialog,
+    CloseCommitDialog,
+    /// Commit what the dialog holds
+    Commit,
+    OpenStagingPane,
+    CloseStagingPane,
+    OpenSnapshotPane,
+    CloseSnapshotPane,
+    /// Run a command in the selected repository
+    RunGitCommand(GitCommand),
+    /// A `GitCommand` finished running
+    CommandFinished {
+        repo: usize,
+        outcome: CommandOutcome,
+    },
+    /// A file's hunks, read for the hunk view, or why they couldn't be
+    Patch {
+        repo: usize,
+        path: PathBuf,
+        staged: bool,
+        patch: Result<FilePatch, String>,
+    },
+    /// A snapshot's diff, read for the snapshot pane
+    SnapshotDiff {
+        repo: usize,
+        name: String,
+        diff: String,
+    },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AppState {
     pub ringing: bool,
     pub snoozed: bool,
     pub snoozed_at: Option<DateTime<Local>>,
     pub snoozed_until: Option<DateTime<Local>>,
+    /// The repository whose details are shown
+    pub selected_repo: usize,
+    /// Open while writing a commit message
+    pub commit_dialog: Option<CommitDialog>,
+    /// Shown in place of the file list while staging
+    pub staging_pane: Option<StagingPane>,
+    /// Shown in place of the file list while browsing snapshots
+    pub snapshot_pane: Option<SnapshotPane>,
 }
 
 impl AppState {
+    #[allow(dead_code)]
     pub fn new(
         ringing: bool,
         snoozed: bool,
         snoozed_at: Option<DateTime<Local>>,
         snoozed_until: Option<DateTime<Local>>,
+        selected_repo: usize,
     ) -> Self {
         Self {
             ringing,
             snoozed,
             snoozed_at,
             snoozed_until,
+            selected_repo,
+            commit_dialog: None,
+            staging_pane: None,
+            snapshot_pane: None,
         }
     }
 
@@ -48,6 +103,10 @@ impl AppState {
             snoozed: false,
             snoozed_at: None,
             snoozed_until: None,
+            selected_repo: 0,
+            commit_dialog: None,
+            staging_pane: None,
+            snapshot_pane: None,
         }
     }
 
@@ -71,30 +130,53 @@ impl AppState {
         self.snoozed = false;
         self.snoozed_at = None;
     }
+    /// Closes the commit dialog after a commit, or shows why a command
+    /// failed. Returns the command that rereads the open hunks, if any.
+    fn finish_command(&mut self, outcome: CommandOutcome) -> Option<GitCommand> {
+        if let Some(dialog) = self.commit_dialog.as_mut() {
+            if !outcome.success {
+                dialog.status = DialogStatus::Failed(outcome.output);
+                return None;
+            }
+            self.commit_dialog = None;
+        }
+        if let Some(snapshot_pane) = self.snapshot_pane.as_mut() {
+            snapshot_pane.message = Some(outcome);
+            return None;
+        }
+        let staging_pane = self.staging_pane.as_mut()?;
+        staging_pane.error = (!outcome.success).then_some(outcome.output);
+        staging_pane.reload()
+    }
+    fn select_next_repo(&mut self, repo_count: usize) {
+        self.selected_repo = (self.selected_repo + 1) % repo_count.max(1);
+    }
 }
 
 pub async fn manager_loop(
     mut rx_app: Receiver<ManagerMessage>,
     tx_ui_manager: Sender<UiMessage>,
     tx_bell_manager: Sender<BellMessage>,
+    tx_git: Vec<Sender<GitCommand>>,
     options: Arc<Options>,
 ) {
-    let mut last_git_state: Arc<Option<GitState>> = Arc::new(None);
+    // The latest state of each repository, by index into `options.repos`
+    let mut git_states: Vec<Option<GitState>> = vec![None; options.repos.len()];
     let app_state = Arc::new(Mutex::new(AppState::default()));
-    let manager_handle = tokio::spawn(async move {
-        while let Some(cmd) = rx_app.recv().await {
+    let snapshot_settings = SnapshotSettings::from_options(&options);
+    tokio::spawn(async move {
+        while let Some(mut cmd) = rx_app.recv().await {
+            if let ManagerMessage::Key(key_event) = cmd {
+                let selected_repo = app_state.lock().unwrap().selected_repo;
+                let git_state = git_states.get(selected_repo).and_then(Option::as_ref);
+                match key_message(key_event, &app_state, git_state) {
+                    Some(message) => cmd = message,
+                    None => continue,
+                }
+            }
             match cmd {
                 ManagerMessage::Redraw => {
-                    let app_state_clone = app_state.clone();
-                    if let Some(git_state) = Arc::clone(&last_git_state).as_ref() {
-                        tx_ui_manager
-                            .send(UiMessage::GitUpdate {
-                                git_state: git_state.clone(),
-                                app_state: app_state_clone,
-                            })
-                            .await
-                            .unwrap();
-                    }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
                 }
                 ManagerMessage::Quit => {
                     disable_raw_mode().unwrap();
@@ -106,22 +188,146 @@ pub async fn manager_loop(
                 // ManagerMessage::Snooze => {
                 //     // execute!(stdout, Clear(ClearType::All)).unwrap();
                 // }
-                ManagerMessage::Git { git_state } => {
-                    if !git_state.compare_with_prev(last_git_state.clone()) {
-                        tx_ui_manager
-                            .send(UiMessage::GitUpdate {
-                                git_state: git_state.clone(),
-                                app_state: app_state.clone(),
-                            })
-                            .await
-                            .unwrap();
+                ManagerMessage::Git { repo, git_state } => {
+                    let git_state = *git_state;
+                    let unchanged = git_states[repo]
+                        .as_ref()
+                        .is_some_and(|prev| git_state.compare(prev));
+                    if !unchanged {
+                        git_states[repo] = Some(git_state);
+                        send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state)
+                            .await;
                         //TODO: Check if bell is already ringing
 
-                        interpret_state_and_send_messages(&tx_bell_manager, &app_state, &git_state)
-                            .await;
-                        last_git_state = Arc::new(Some(git_state.clone()));
+                        let started_ringing = interpret_state_and_send_messages(
+                            &tx_bell_manager,
+                            &app_state,
+                            &known_states(&git_states),
+                        )
+                        .await;
+                        if started_ringing && snapshot_settings.enabled && snapshot_settings.on_bell
+                        {
+                            for (repo, git_state) in git_states.iter().enumerate() {
+                                if git_state.as_ref().is_some_and(GitState::wants_attention) {
+                                    tx_git[repo].send(GitCommand::TakeSnapshot).await.unwrap();
+                                }
+                            }
+                        }
+                    }
+                }
+                ManagerMessage::NextRepo => {
+                    {
+                        app_state
+                            .as_ref()
+                            .lock()
+                            .unwrap()
+                            .select_next_repo(git_states.len());
+                    }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::OpenCommitDialog => {
+                    let selected_repo = app_state.lock().unwrap().selected_repo;
+                    let Some(git_state) = git_states.get(selected_repo).and_then(Option::as_ref)
+                    else {
+                        continue;
+                    };
+                    let dialog = CommitDialog::new(git_state);
+                    app_state.lock().unwrap().commit_dialog = Some(dialog);
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::CloseCommitDialog => {
+                    app_state.lock().unwrap().commit_dialog = None;
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::Commit => {
+                    let (repo, command) = {
+                        let app_state = app_state.lock().unwrap();
+                        let Some(dialog) = app_state.commit_dialog.as_ref() else {
+                            continue;
+                        };
+                        let command = GitCommand::Commit {
+                            message: dialog.message(),
+                            all: dialog.scope == CommitScope::All,
+                        };
+                        (app_state.selected_repo, command)
+                    };
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                    tx_git[repo].send(command).await.unwrap();
+                }
+                ManagerMessage::OpenStagingPane => {
+                    {
+                        let mut app_state = app_state.lock().unwrap();
+                        app_state.snapshot_pane = None;
+                        app_state.staging_pane = Some(StagingPane::default());
+                    }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::OpenSnapshotPane if snapshot_settings.enabled => {
+                    {
+                        let mut app_state = app_state.lock().unwrap();
+                        app_state.staging_pane = None;
+                        app_state.snapshot_pane = Some(SnapshotPane::default());
                     }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::OpenSnapshotPane => {}
+                ManagerMessage::CloseSnapshotPane => {
+                    app_state.lock().unwrap().snapshot_pane = None;
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::SnapshotDiff { repo, name, diff } => {
+                    {
+                        let mut app_state = app_state.lock().unwrap();
+                        if repo == app_state.selected_repo {
+                            if let Some(snapshot_pane) = app_state.snapshot_pane.as_mut() {
+                                snapshot_pane.set_diff(&name, diff);
+                            }
+                        }
+                    }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::CloseStagingPane => {
+                    app_state.lock().unwrap().staging_pane = None;
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                ManagerMessage::RunGitCommand(command) => {
+                    let repo = app_state.lock().unwrap().selected_repo;
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                    tx_git[repo].send(command).await.unwrap();
                 }
+                ManagerMessage::CommandFinished { repo, outcome } => {
+                    let reload = {
+                        let mut app_state = app_state.lock().unwrap();
+                        // The dialog and the pane belong to the selected repository
+                        if repo == app_state.selected_repo {
+                            app_state.finish_command(outcome)
+                        } else {
+                            None
+                        }
+                    };
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                    if let Some(reload) = reload {
+                        tx_git[repo].send(reload).await.unwrap();
+                    }
+                }
+                ManagerMessage::Patch {
+                    repo,
+                    path,
+                    staged,
+                    patch,
+                } => {
+                    {
+                        let mut app_state = app_state.lock().unwrap();
+                        if repo == app_state.selected_repo {
+                            if let Some(staging_pane) = app_state.staging_pane.as_mut() {
+                                staging_pane.set_patch(&path, staged, patch);
+                            }
+                        }
+                    }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
+                }
+                // Turned into one of the messages above before matching
+                ManagerMessage::Key(_) => {}
                 ManagerMessage::Bell => {
                     tx_bell_manager.send(BellMessage::Start).await.unwrap();
                     tokio::time::sleep(Duration::from_secs(3)).await;
@@ -134,15 +340,7 @@ pub async fn manager_loop(
                         app_state.as_ref().lock().unwrap().snooze();
                     }
                     tx_bell_manager.send(BellMessage::Stop).await.unwrap();
-                    if let Some(git_state) = Arc::clone(&last_git_state).as_ref() {
-                        tx_ui_manager
-                            .send(UiMessage::GitUpdate {
-                                git_state: git_state.clone(),
-                                app_state: app_state.clone(),
-                            })
-                            .await
-                            .unwrap();
-                    }
+                    send_ui_update(&tx_ui_manager, &git_states, &options.repos, &app_state).await;
                     let app_state_clone = app_state.clone();
                     tokio::spawn(async move {
                         tokio::time::sleep(snooze_time).await;
@@ -151,29 +349,254 @@ pub async fn manager_loop(
                         }
                     });
 
-                    if let Some(git_state) = Arc::clone(&last_git_state).as_ref() {
-                        interpret_state_and_send_messages(&tx_bell_manager, &app_state, &git_state)
-                            .await;
-                    }
+                    interpret_state_and_send_messages(
+                        &tx_bell_manager,
+                        &app_state,
+                        &known_states(&git_states),
+                    )
+                    .await;
                 }
             }
         }
     });
 }
 
+/// What a key press means: typing while the commit dialog is open, a
+/// staging command while the staging pane is, otherwise one of the
+/// single-key commands.
+fn key_message(
+    key_event: KeyEvent,
+    app_state: &Arc<Mutex<AppState>>,
+    git_state: Option<&GitState>,
+) -> Option<ManagerMessage> {
+    let mut app_state = app_state.lock().unwrap();
+    if let Some(dialog) = app_state.commit_dialog.as_mut() {
+        return Some(match dialog.handle_key(key_event) {
+            DialogAction::None => ManagerMessage::Redraw,
+            DialogAction::Commit => ManagerMessage::Commit,
+            DialogAction::Close => ManagerMessage::CloseCommitDialog,
+        });
+    }
+    if let Some(snapshot_pane) = app_state.snapshot_pane.as_mut() {
+        let snapshots = git_state.map_or(&[][..], |git_state| &git_state.snapshots);
+        return Some(match snapshot_pane.handle_key(key_event, snapshots) {
+            SnapshotAction::None => ManagerMessage::Redraw,
+            SnapshotAction::Run(command) => ManagerMessage::RunGitCommand(command),
+            SnapshotAction::Close => ManagerMessage::CloseSnapshotPane,
+        });
+    }
+    if let Some(staging_pane) = app_state.staging_pane.as_mut() {
+        let entries = git_state.map(staging::entries).unwrap_or_default();
+        return Some(match staging_pane.handle_key(key_event, &entries) {
+            StagingAction::None => ManagerMessage::Redraw,
+            StagingAction::Run(command) => ManagerMessage::RunGitCommand(command),
+            StagingAction::Commit => ManagerMessage::OpenCommitDialog,
+            StagingAction::Close => ManagerMessage::CloseStagingPane,
+        });
+    }
+
+    match key_event.code {
+        KeyCode::Char('q') => Some(ManagerMessage::Quit),
+        KeyCode::Char(' ') => Some(ManagerMessage::Snooze),
+        KeyCode::Char('b') => Some(ManagerMessage::Bell),
+        KeyCode::Char('c') => Some(ManagerMessage::OpenCommitDialog),
+        KeyCode::Char('s') => Some(ManagerMessage::OpenStagingPane),
+        KeyCode::Char('w') => Some(ManagerMessage::OpenSnapshotPane),
+        KeyCode::Tab => Some(ManagerMessage::NextRepo),
+        _ => None,
+    }
+}
+
+fn known_states(git_states: &[Option<GitState>]) -> Vec<GitState> {
+    git_states.iter().flatten().cloned().collect()
+}
+
+/// Redraws with a stand-in for each repository that hasn't reported in
+/// yet, so the UI can index the states the same way as `options.repos`
+/// and one slow repository doesn't hold up the rest.
+async fn send_ui_update(
+    tx_ui_manager: &Sender<UiMessage>,
+    git_states: &[Option<GitState>],
+    repos: &[RepoOptions],
+    app_state: &Arc<Mutex<AppState>>,
+) {
+    let git_states = git_states
+        .iter()
+        .zip(repos)
+        .map(|(git_state, repo)| git_state.clone().unwrap_or_else(|| GitState::waiting(repo)))
+        .collect();
+    tx_ui_manager
+        .send(UiMessage::GitUpdate {
+            git_states,
+            app_state: app_state.clone(),
+        })
+        .await
+        .unwrap();
+}
+
+/// Rings while any repository is above its threshold or has a reminder due.
+/// Returns whether the bell just started ringing.
 async fn interpret_state_and_send_messages(
     tx_bell_manager: &Sender<BellMessage>,
     app_state: &Arc<Mutex<AppState>>,
-    git_state: &GitState,
-) {
+    git_states: &[GitState],
+) -> bool {
     let is_ringing = app_state.as_ref().lock().unwrap().is_ringing();
     let is_snoozed = app_state.as_ref().lock().unwrap().snoozed;
+    let wants_attention = git_states.iter().any(GitState::wants_attention);
 
-    if git_state.is_above_threshold() && !is_ringing && !is_snoozed {
+    if wants_attention && !is_ringing && !is_snoozed {
         tx_bell_manager.send(BellMessage::Start).await.unwrap();
         app_state.as_ref().lock().unwrap().start_ringing();
-    } else if (!git_state.is_above_threshold() && is_ringing) || is_snoozed {
+        return true;
+    } else if (!wants_attention && is_ringing) || is_snoozed {
         tx_bell_manager.send(BellMessage::Stop).await.unwrap();
         app_state.as_ref().lock().unwrap().stop_ringing();
     }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{CountSettings, FileChange, FileStatus, MockBackend, RepoStatus};
+
+    fn git_state_with_total(total: i32) -> GitState {
+        let backend = MockBackend {
+            commit: "c0ffee".to_string(),
+            combined: vec![FileChange::new(
+                "src/lib.rs",
+                total,
+                0,
+                FileStatus::Modified,
+            )],
+            ..MockBackend::default()
+        };
+        let mut git_state = GitState::new(100);
+        git_state
+            .update(&backend, &CountSettings::default())
+            .unwrap();
+        git_state
+    }
+
+    #[tokio::test]
+    async fn rings_when_above_threshold_and_stops_when_back_under() {
+        let (tx_bell, mut rx_bell) = tokio::sync::mpsc::channel::<BellMessage>(8);
+        let app_state = Arc::new(Mutex::new(AppState::default()));
+
+        interpret_state_and_send_messages(&tx_bell, &app_state, &[git_state_with_total(150)]).await;
+        assert!(matches!(rx_bell.try_recv(), Ok(BellMessage::Start)));
+        assert!(app_state.lock().unwrap().is_ringing());
+
+        interpret_state_and_send_messages(&tx_bell, &app_state, &[git_state_with_total(50)]).await;
+        assert!(matches!(rx_bell.try_recv(), Ok(BellMessage::Stop)));
+        assert!(!app_state.lock().unwrap().is_ringing());
+    }
+
+    #[tokio::test]
+    async fn does_not_ring_while_snoozed() {
+        let (tx_bell, mut rx_bell) = tokio::sync::mpsc::channel::<BellMessage>(8);
+        let app_state = Arc::new(Mutex::new(AppState::default()));
+        app_state.lock().unwrap().snooze();
+
+        interpret_state_and_send_messages(&tx_bell, &app_state, &[git_state_with_total(150)]).await;
+        assert!(!matches!(rx_bell.try_recv(), Ok(BellMessage::Start)));
+    }
+
+    #[tokio::test]
+    async fn rings_while_any_repo_is_above_its_threshold() {
+        let (tx_bell, mut rx_bell) = tokio::sync::mpsc::channel::<BellMessage>(8);
+        let app_state = Arc::new(Mutex::new(AppState::default()));
+        let quiet = git_state_with_total(50);
+
+        interpret_state_and_send_messages(
+            &tx_bell,
+            &app_state,
+            &[quiet.clone(), git_state_with_total(150)],
+        )
+        .await;
+        assert!(matches!(rx_bell.try_recv(), Ok(BellMessage::Start)));
+
+        interpret_state_and_send_messages(&tx_bell, &app_state, &[quiet.clone(), quiet]).await;
+        assert!(matches!(rx_bell.try_recv(), Ok(BellMessage::Stop)));
+    }
+
+    #[tokio::test]
+    async fn draws_the_repos_that_have_reported_without_the_rest() {
+        let (tx_ui, mut rx_ui) = tokio::sync::mpsc::channel::<UiMessage>(8);
+        let app_state = Arc::new(Mutex::new(AppState::default()));
+        let repos = [
+            RepoOptions {
+                path: PathBuf::from("/src/app"),
+                threshold: 100,
+                own_threshold: false,
+            },
+            RepoOptions {
+                path: PathBuf::from("/src/lib"),
+                threshold: 50,
+                own_threshold: true,
+            },
+        ];
+
+        // The second repository never reports
+        send_ui_update(
+            &tx_ui,
+            &[Some(git_state_with_total(150)), None],
+            &repos,
+            &app_state,
+        )
+        .await;
+
+        let Ok(UiMessage::GitUpdate { git_states, .. }) = rx_ui.try_recv() else {
+            panic!("nothing was drawn");
+        };
+        assert_eq!(git_states.len(), 2);
+        assert_eq!(git_states[0].git_changes.total, 150);
+        assert_eq!(git_states[1].repo_status, RepoStatus::Waiting);
+        assert_eq!(git_states[1].repo_root, PathBuf::from("/src/lib"));
+        assert_eq!(git_states[1].threshold, 50);
+    }
+
+    #[test]
+    fn keys_go_to_the_commit_dialog_while_it_is_open() {
+        let app_state = Arc::new(Mutex::new(AppState::default()));
+        let key = |c| KeyEvent::new(KeyCode::Char(c), crossterm::event::KeyModifiers::NONE);
+
+        assert!(matches!(
+            key_message(key('c'), &app_state, None),
+            Some(ManagerMessage::OpenCommitDialog)
+        ));
+
+        app_state.lock().unwrap().commit_dialog = Some(CommitDialog::default());
+        assert!(matches!(
+            key_message(key('q'), &app_state, None),
+            Some(ManagerMessage::Redraw)
+        ));
+        let message = app_state
+            .lock()
+            .unwrap()
+            .commit_dialog
+            .as_ref()
+            .unwrap()
+            .message();
+        assert_eq!(message, "q");
+    }
+
+    #[test]
+    fn a_failed_commit_keeps_the_dialog_open_with_the_output() {
+        let mut app_state = AppState::default();
+        app_state.commit_dialog = Some(CommitDialog::default());
+
+        app_state.finish_command(CommandOutcome::failed("pre-commit hook failed"));
+        assert_eq!(
+            app_state.commit_dialog.as_ref().unwrap().status,
+            DialogStatus::Failed("pre-commit hook failed".to_string())
+        );
+
+        app_state.finish_command(CommandOutcome {
+            success: true,
+            output: String::new(),
+        });
+        assert!(app_state.commit_dialog.is_none());
+    }
 }
@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::git::FileChange;
+
+/// A multi-step git command that's waiting for the user to finish it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+impl Operation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::Merge => "merge",
+            Operation::Rebase => "rebase",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+            Operation::Bisect => "bisect",
+        }
+    }
+}
+
+/// The files git leaves in the git directory while each operation is in
+/// progress. A rebase that stops on a conflicted pick also leaves
+/// `CHERRY_PICK_HEAD`, so rebases are checked first.
+const OPERATION_FILES: [(&str, Operation); 6] = [
+    ("rebase-merge", Operation::Rebase),
+    ("rebase-apply", Operation::Rebase),
+    ("MERGE_HEAD", Operation::Merge),
+    ("CHERRY_PICK_HEAD", Operation::CherryPick),
+    ("REVERT_HEAD", Operation::Revert),
+    ("BISECT_LOG", Operation::Bisect),
+];
+
+pub fn in_progress(git_dir: &Path) -> Option<Operation> {
+    OPERATION_FILES
+        .iter()
+        .find(|(name, _)| git_dir.join(name).exists())
+        .map(|(_, operation)| *operation)
+}
+
+/// Whether `relative`, a path inside the git directory, marks an operation
+/// starting or finishing.
+pub fn is_operation_path(relative: &Path) -> bool {
+    OPERATION_FILES
+        .iter()
+        .any(|(name, _)| relative.starts_with(name))
+}
+
+/// The changed files that still have conflict markers in them.
+pub fn files_with_conflict_markers(files: &[FileChange], workdir: &Path) -> Vec<PathBuf> {
+    files
+        .iter()
+        .filter(|file| !file.binary)
+        .filter(|file| has_conflict_markers(&workdir.join(&file.path)))
+        .map(|file| file.path.clone())
+        .collect()
+}
+
+/// Looks for a `<<<<<<<` line followed later by a `>>>>>>>` line, so a
+/// lone `=======` underline doesn't count.
+fn has_conflict_markers(path: &Path) -> bool {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+
+    let mut in_conflict = false;
+    for line in BufReader::new(file).split(b'\n').map_while(Result::ok) {
+        if line.starts_with(b"<<<<<<< ") || line == b"<<<<<<<" {
+            in_conflict = true;
+        } else if in_conflict && (line.starts_with(b">>>>>>> ") || line == b">>>>>>>") {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::git::FileStatus;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("diffding-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn detects_operations_from_the_git_directory() {
+        let git_dir = scratch_dir("operations");
+        assert_eq!(in_progress(&git_dir), None);
+
+        fs::write(git_dir.join("CHERRY_PICK_HEAD"), "c0ffee\n").unwrap();
+        assert_eq!(in_progress(&git_dir), Some(Operation::CherryPick));
+
+        fs::create_dir(git_dir.join("rebase-merge")).unwrap();
+        assert_eq!(in_progress(&git_dir), Some(Operation::Rebase));
+
+        fs::remove_dir_all(&git_dir).unwrap();
+    }
+
+    #[test]
+    fn finds_conflict_markers() {
+        let workdir = scratch_dir("conflicts");
+        fs::write(
+            workdir.join("conflicted.rs"),
+            "fn main() {\n<<<<<<< HEAD\n    a();\n=======\n    b();\n>>>>>>> topic\n}\n",
+        )
+        .unwrap();
+        fs::write(workdir.join("README.md"), "Title\n=======\n").unwrap();
+        let files = vec![
+            FileChange::new("conflicted.rs", 5, 0, FileStatus::Unmerged),
+            FileChange::new("README.md", 2, 0, FileStatus::Modified),
+        ];
+
+        assert_eq!(
+            files_with_conflict_markers(&files, &workdir),
+            vec![PathBuf::from("conflicted.rs")]
+        );
+
+        fs::remove_dir_all(&workdir).unwrap();
+    }
+}
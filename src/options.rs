@@ -1,23 +1,43 @@
 use std::collections::HashMap;
 use std::env;
 use std::error::Error;
+use std::fs;
+use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use config::{Config, File};
+use config::{Config, File, Value};
+use serde_derive::Deserialize;
 
-use crate::Options;
+use crate::commands;
+use crate::git::{BackendKind, CountMode};
+use crate::{Options, RepoOptions, DEFAULT_UNTRACKED_MAX_SIZE};
+
+/// An entry in `repos`: either a path, or a table with a path and its own threshold.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RepoEntry {
+    Path(String),
+    Table {
+        path: String,
+        threshold: Option<i32>,
+    },
+}
 
 pub fn get_options() -> Result<Arc<Options>, Box<dyn Error>> {
     let config_path = get_config_path();
 
-    let settings = Config::builder()
+    let config = Config::builder()
         .add_source(File::from(config_path.join("config.toml")).required(false))
         .build()?;
 
-    let settings = settings
-        .try_deserialize::<HashMap<String, String>>()
-        .unwrap();
+    // Scalar settings as strings; lists are read from `config` directly
+    let settings: HashMap<String, String> = config
+        .clone()
+        .try_deserialize::<HashMap<String, Value>>()?
+        .into_iter()
+        .filter_map(|(key, value)| value.into_string().ok().map(|value| (key, value)))
+        .collect();
 
     let config_options = Options {
         sound_path: settings.get("sound").map(|sound| config_path.join(sound)),
@@ -36,36 +56,237 @@ pub fn get_options() -> Result<Arc<Options>, Box<dyn Error>> {
             .unwrap_or(&"".to_string())
             .parse::<f32>()
             .unwrap_or(1.0),
-        /// Snooze time in minutes, converted to seconds
+        // Snooze time in minutes, converted to seconds
         snooze_length: settings
             .get("snooze_length")
             .unwrap_or(&"".to_string())
             .parse::<i64>()
             .unwrap_or(5)
             * 60,
+        count_mode: settings
+            .get("count")
+            .and_then(|count| count.parse::<CountMode>().ok())
+            .unwrap_or_default(),
+        // Max size in kilobytes, converted to bytes
+        untracked_max_size: settings
+            .get("untracked_max_size")
+            .and_then(|size| size.parse::<u64>().ok())
+            .map_or(DEFAULT_UNTRACKED_MAX_SIZE, |size| size * 1024),
+        backend: settings
+            .get("backend")
+            .and_then(|backend| backend.parse::<BackendKind>().ok())
+            .unwrap_or_default(),
+        watch: settings
+            .get("watch")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(true),
+        // Safety interval in seconds, converted to milliseconds
+        safety_interval: settings
+            .get("safety_interval")
+            .unwrap_or(&"".to_string())
+            .parse::<u64>()
+            .unwrap_or(60)
+            * 1000,
+        include: config.get::<Vec<String>>("include").unwrap_or_default(),
+        exclude: config.get::<Vec<String>>("exclude").unwrap_or_default(),
+        skip_generated: settings
+            .get("skip_generated")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(true),
+        insertion_weight: settings
+            .get("insertion_weight")
+            .unwrap_or(&"".to_string())
+            .parse::<f64>()
+            .unwrap_or(1.0),
+        deletion_weight: settings
+            .get("deletion_weight")
+            .unwrap_or(&"".to_string())
+            .parse::<f64>()
+            .unwrap_or(1.0),
+        weights: config
+            .get::<HashMap<String, f64>>("weights")
+            .unwrap_or_default(),
+        ignore_whitespace: settings
+            .get("ignore_whitespace")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(false),
+        submodules: settings
+            .get("submodules")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(false),
+        submodule_include: config
+            .get::<Vec<String>>("submodule_include")
+            .unwrap_or_default(),
+        submodule_exclude: config
+            .get::<Vec<String>>("submodule_exclude")
+            .unwrap_or_default(),
+        max_unpushed_commits: settings
+            .get("max_unpushed_commits")
+            .and_then(|commits| commits.parse::<usize>().ok()),
+        max_unpushed_hours: settings
+            .get("max_unpushed_hours")
+            .and_then(|hours| hours.parse::<f64>().ok()),
+        max_dirty_minutes: settings
+            .get("max_dirty_minutes")
+            .and_then(|minutes| minutes.parse::<i64>().ok()),
+        base_branch: settings
+            .get("base_branch")
+            .cloned()
+            .unwrap_or_else(|| "main".to_string()),
+        max_branch_size: settings
+            .get("max_branch_size")
+            .and_then(|size| size.parse::<i32>().ok()),
+        // Max size in kilobytes, converted to bytes
+        max_binary_size: settings
+            .get("max_binary_size")
+            .and_then(|size| size.parse::<u64>().ok())
+            .map(|size| size * 1024),
+        snapshots: settings
+            .get("snapshots")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(false),
+        snapshot_interval: settings
+            .get("snapshot_interval")
+            .and_then(|minutes| minutes.parse::<i64>().ok()),
+        snapshot_on_bell: settings
+            .get("snapshot_on_bell")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(true),
+        adaptive_threshold: settings
+            .get("adaptive_threshold")
+            .unwrap_or(&"".to_string())
+            .parse::<bool>()
+            .unwrap_or(false),
+        adaptive_percentile: settings
+            .get("adaptive_percentile")
+            .unwrap_or(&"".to_string())
+            .parse::<f64>()
+            .unwrap_or(75.0),
+        adaptive_commits: settings
+            .get("adaptive_commits")
+            .unwrap_or(&"".to_string())
+            .parse::<usize>()
+            .unwrap_or(50),
+        repos: Vec::new(),
+        repo: None,
+        subcommand: Vec::new(),
     };
+    let repo_entries = config.get::<Vec<RepoEntry>>("repos").unwrap_or_default();
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let repo = take_flag(&mut args, "--repo")?;
+    // Everything from a subcommand's name on belongs to the subcommand
+    let subcommand = match args.first() {
+        Some(name) if commands::is_subcommand(name) => std::mem::take(&mut args),
+        _ => Vec::new(),
+    };
 
     let options: Options = match args.len() {
-        1 => config_options,
-        2 => Options {
-            git_update_time: args[1].parse::<u64>().unwrap(),
-            sound_path: config_options.sound_path,
-            threshold: config_options.threshold,
-            volume: config_options.volume,
-            snooze_length: config_options.snooze_length,
+        0 => config_options,
+        1 => Options {
+            git_update_time: args[0].parse::<u64>().unwrap(),
+            ..config_options
         },
         _ => Options {
-            git_update_time: args[1].parse::<u64>().unwrap(),
-            sound_path: config_options.sound_path,
-            threshold: args[2].parse::<i32>().unwrap(),
-            volume: config_options.volume,
-            snooze_length: config_options.snooze_length,
+            git_update_time: args[0].parse::<u64>().unwrap(),
+            threshold: args[1].parse::<i32>().unwrap(),
+            ..config_options
         },
     };
 
-    Ok(Arc::new(options))
+    // `--repo` replaces the configured list; with neither, watch the current
+    // directory. Subcommands keep the list to look up the repository's threshold.
+    let repo_entries = match &repo {
+        Some(path) if subcommand.is_empty() => vec![RepoEntry::Path(path.clone())],
+        _ if repo_entries.is_empty() => vec![RepoEntry::Path(".".to_string())],
+        _ => repo_entries,
+    };
+    let repos = repo_entries
+        .into_iter()
+        .map(|entry| {
+            let (path, threshold) = match entry {
+                RepoEntry::Path(path) => (path, None),
+                RepoEntry::Table { path, threshold } => (path, threshold),
+            };
+            Ok(RepoOptions {
+                path: resolve_path(&path)?,
+                threshold: threshold.unwrap_or(options.threshold),
+                own_threshold: threshold.is_some(),
+            })
+        })
+        .collect::<Result<_, Box<dyn Error>>>()?;
+
+    Ok(Arc::new(Options {
+        repos,
+        repo: repo.as_deref().map(resolve_path).transpose()?,
+        subcommand,
+        ..options
+    }))
+}
+
+/// Expands a leading `~` and makes relative paths absolute.
+fn resolve_path(path: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return Ok(PathBuf::from(env::var("HOME")?).join(rest));
+    }
+    Ok(env::current_dir()?.join(path).components().collect())
+}
+
+/// Removes `--name <value>` or `--name=<value>` from `args` and returns the value.
+fn take_flag(args: &mut Vec<String>, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(index) = args
+        .iter()
+        .position(|arg| arg == name || arg.starts_with(&format!("{name}=")))
+    else {
+        return Ok(None);
+    };
+
+    let flag = args.remove(index);
+    if let Some((_, value)) = flag.split_once('=') {
+        return Ok(Some(value.to_string()));
+    }
+    if index < args.len() {
+        return Ok(Some(args.remove(index)));
+    }
+    Err(format!("{name} needs a value").into())
+}
+
+/// Sets a top-level `key = value` in the config file, creating the file if
+/// needed, and returns its path.
+pub fn write_setting(key: &str, value: &str) -> io::Result<PathBuf> {
+    let config_path = get_config_path();
+    fs::create_dir_all(&config_path)?;
+    let path = config_path.join("config.toml");
+    let config = fs::read_to_string(&path).unwrap_or_default();
+    fs::write(&path, set_setting(&config, key, value))?;
+    Ok(path)
+}
+
+/// `config` with `key` set to `value`, replacing the existing setting before
+/// the first table or adding one at the top. Everything else is kept as is.
+fn set_setting(config: &str, key: &str, value: &str) -> String {
+    let setting = format!("{key} = {value}");
+    let mut lines: Vec<&str> = config.lines().collect();
+    let top_level = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing = lines[..top_level].iter().position(|line| {
+        line.split_once('=')
+            .is_some_and(|(name, _)| name.trim() == key)
+    });
+
+    match existing {
+        Some(index) => lines[index] = &setting,
+        None => lines.insert(0, &setting),
+    }
+    lines.join("\n") + "\n"
 }
 
 fn get_config_path() -> PathBuf {
@@ -75,3 +296,27 @@ fn get_config_path() -> PathBuf {
     config_path.push("diffding");
     config_path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setting_replaces_the_top_level_value() {
+        let config = "# Ring sooner\nthreshold = 100\nvolume = 0.5\n\n[weights]\nthreshold = 2\n";
+
+        assert_eq!(
+            set_setting(config, "threshold", "60"),
+            "# Ring sooner\nthreshold = 60\nvolume = 0.5\n\n[weights]\nthreshold = 2\n"
+        );
+    }
+
+    #[test]
+    fn setting_is_added_at_the_top_when_missing() {
+        assert_eq!(
+            set_setting("[weights]\n\"*.md\" = 0.5\n", "threshold", "60"),
+            "threshold = 60\n[weights]\n\"*.md\" = 0.5\n"
+        );
+        assert_eq!(set_setting("", "threshold", "60"), "threshold = 60\n");
+    }
+}
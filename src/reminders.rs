@@ -0,0 +1,288 @@
+use chrono::{DateTime, Duration, Local, TimeZone};
+
+use crate::git::GitState;
+use crate::Options;
+
+/// Reasons to ring besides the line count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reminder {
+    /// More commits than allowed haven't been pushed
+    UnpushedCommits { commits: usize },
+    /// Unpushed work older than allowed; `since` is the oldest unpushed
+    /// commit's time, in seconds since the epoch
+    UnpushedWork { since: i64 },
+    /// The worktree has been dirty for longer than allowed
+    DirtyTooLong { since: i64 },
+    /// The branch has grown past the size that wants a review
+    BranchTooBig { lines: i32 },
+    /// More binary changes, in bytes, than allowed
+    BinaryChanges { bytes: u64 },
+}
+
+impl Reminder {
+    /// The big text shown when this is the reason for ringing.
+    pub fn headline(&self) -> &'static str {
+        match self {
+            Reminder::UnpushedCommits { .. } | Reminder::UnpushedWork { .. } => "PUSH!",
+            Reminder::DirtyTooLong { .. } => "COMMIT!",
+            Reminder::BranchTooBig { .. } => "OPEN PR!",
+            Reminder::BinaryChanges { .. } => "BINARY!",
+        }
+    }
+
+    pub fn message(&self, now: DateTime<Local>) -> String {
+        match self {
+            Reminder::UnpushedCommits { commits } => format!("{commits} unpushed commits"),
+            Reminder::UnpushedWork { since } => {
+                format!("unpushed work from {} ago", format_age(*since, now))
+            }
+            Reminder::DirtyTooLong { since } => {
+                format!("uncommitted changes for {}", format_age(*since, now))
+            }
+            Reminder::BranchTooBig { lines } => {
+                format!("branch has {lines} changed lines, time for a pull request")
+            }
+            Reminder::BinaryChanges { bytes } => {
+                format!("{} of binary changes", format_bytes(*bytes))
+            }
+        }
+    }
+}
+
+/// The limits past which a reminder fires. `None` turns a reminder off.
+#[derive(Debug, Clone, Default)]
+pub struct ReminderSettings {
+    pub max_unpushed_commits: Option<usize>,
+    pub max_unpushed_age: Option<Duration>,
+    pub max_dirty_age: Option<Duration>,
+    /// Compared against the branch size's score
+    pub max_branch_size: Option<i32>,
+    pub max_binary_bytes: Option<u64>,
+}
+
+impl ReminderSettings {
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            max_unpushed_commits: options.max_unpushed_commits,
+            max_unpushed_age: options
+                .max_unpushed_hours
+                .map(|hours| Duration::seconds((hours * 3600.0) as i64)),
+            max_dirty_age: options.max_dirty_minutes.map(Duration::minutes),
+            max_branch_size: options.max_branch_size,
+            max_binary_bytes: options.max_binary_size,
+        }
+    }
+
+    /// The reminders due for `git_state` at `now`.
+    pub fn check(&self, git_state: &GitState, now: DateTime<Local>) -> Vec<Reminder> {
+        let mut reminders = Vec::new();
+
+        if let (Some(max_age), Some(since)) = (self.max_dirty_age, git_state.dirty_since) {
+            if now.timestamp() - since > max_age.num_seconds() {
+                reminders.push(Reminder::DirtyTooLong { since });
+            }
+        }
+
+        if let (Some(max_size), Some(branch_size)) = (self.max_branch_size, &git_state.branch_size)
+        {
+            if branch_size.score > f64::from(max_size) {
+                reminders.push(Reminder::BranchTooBig {
+                    lines: branch_size.changes.total,
+                });
+            }
+        }
+
+        if let Some(max_bytes) = self.max_binary_bytes {
+            let bytes = git_state.git_changes.binary.bytes;
+            if bytes > max_bytes {
+                reminders.push(Reminder::BinaryChanges { bytes });
+            }
+        }
+
+        let Some(upstream) = &git_state.branch.upstream else {
+            return reminders;
+        };
+
+        if let Some(max_commits) = self.max_unpushed_commits {
+            if upstream.ahead > max_commits {
+                reminders.push(Reminder::UnpushedCommits {
+                    commits: upstream.ahead,
+                });
+            }
+        }
+
+        if let (Some(max_age), Some(since)) = (self.max_unpushed_age, upstream.oldest_unpushed) {
+            if now.timestamp() - since > max_age.num_seconds() {
+                reminders.push(Reminder::UnpushedWork { since });
+            }
+        }
+
+        reminders
+    }
+}
+
+/// How long ago `since` (in seconds since the epoch) was, like `47 min`
+/// or `2h 5m`.
+pub fn format_age(since: i64, now: DateTime<Local>) -> String {
+    let age = Local
+        .timestamp_opt(since, 0)
+        .single()
+        .map_or(Duration::zero(), |since| now - since);
+
+    if age.num_minutes() < 1 {
+        "less than a minute".to_string()
+    } else if age.num_hours() < 1 {
+        format!("{} min", age.num_minutes())
+    } else if age.num_days() < 1 {
+        format!("{}h {}m", age.num_hours(), age.num_minutes() % 60)
+    } else if age.num_days() == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", age.num_days())
+    }
+}
+
+/// A size in bytes, like `840 B` or `1.5 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{BranchSize, BranchState, ByteStat, DiffStat, GitChanges, Upstream};
+
+    fn git_state_with_upstream(ahead: usize, oldest_unpushed: Option<i64>) -> GitState {
+        GitState {
+            branch: BranchState {
+                name: Some("main".to_string()),
+                upstream: Some(Upstream {
+                    name: "origin/main".to_string(),
+                    ahead,
+                    behind: 0,
+                    oldest_unpushed,
+                }),
+            },
+            ..GitState::default()
+        }
+    }
+
+    #[test]
+    fn fires_past_the_unpushed_commit_limit() {
+        let settings = ReminderSettings {
+            max_unpushed_commits: Some(3),
+            ..ReminderSettings::default()
+        };
+        let now = Local::now();
+
+        assert!(settings
+            .check(&git_state_with_upstream(3, None), now)
+            .is_empty());
+        assert_eq!(
+            settings.check(&git_state_with_upstream(4, None), now),
+            vec![Reminder::UnpushedCommits { commits: 4 }]
+        );
+    }
+
+    #[test]
+    fn fires_when_unpushed_work_is_too_old() {
+        let settings = ReminderSettings {
+            max_unpushed_age: Some(Duration::hours(2)),
+            ..ReminderSettings::default()
+        };
+        let now = Local::now();
+        let recent = (now - Duration::hours(1)).timestamp();
+        let old = (now - Duration::hours(3)).timestamp();
+
+        assert!(settings
+            .check(&git_state_with_upstream(1, Some(recent)), now)
+            .is_empty());
+        assert_eq!(
+            settings.check(&git_state_with_upstream(1, Some(old)), now),
+            vec![Reminder::UnpushedWork { since: old }]
+        );
+        assert!(settings.check(&GitState::default(), now).is_empty());
+    }
+
+    #[test]
+    fn fires_when_dirty_for_too_long() {
+        let settings = ReminderSettings {
+            max_dirty_age: Some(Duration::minutes(90)),
+            ..ReminderSettings::default()
+        };
+        let now = Local::now();
+        let since = (now - Duration::hours(2)).timestamp();
+        let git_state = GitState {
+            dirty_since: Some(since),
+            ..GitState::default()
+        };
+
+        assert_eq!(
+            settings.check(&git_state, now),
+            vec![Reminder::DirtyTooLong { since }]
+        );
+        assert_eq!(format_age(since, now), "2h 0m");
+    }
+
+    #[test]
+    fn fires_when_the_branch_outgrows_its_limit() {
+        let settings = ReminderSettings {
+            max_branch_size: Some(400),
+            ..ReminderSettings::default()
+        };
+        let now = Local::now();
+        let mut git_state = GitState {
+            branch_size: Some(BranchSize {
+                base: "main".to_string(),
+                changes: DiffStat::new(300, 100),
+                score: 400.0,
+                since: now.timestamp(),
+            }),
+            ..GitState::default()
+        };
+        assert!(settings.check(&git_state, now).is_empty());
+
+        git_state.branch_size.as_mut().unwrap().score = 420.0;
+        assert_eq!(
+            settings.check(&git_state, now),
+            vec![Reminder::BranchTooBig { lines: 400 }]
+        );
+    }
+
+    #[test]
+    fn fires_past_the_binary_size_limit() {
+        let settings = ReminderSettings {
+            max_binary_bytes: Some(10 * 1024 * 1024),
+            ..ReminderSettings::default()
+        };
+        let bytes = 200 * 1024 * 1024;
+        let git_state = GitState {
+            git_changes: GitChanges {
+                binary: ByteStat { files: 1, bytes },
+                ..GitChanges::default()
+            },
+            ..GitState::default()
+        };
+
+        assert_eq!(
+            settings.check(&git_state, Local::now()),
+            vec![Reminder::BinaryChanges { bytes }]
+        );
+        assert!(settings
+            .check(&GitState::default(), Local::now())
+            .is_empty());
+        assert_eq!(format_bytes(bytes), "200.0 MB");
+        assert_eq!(format_bytes(840), "840 B");
+    }
+}
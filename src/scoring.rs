@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use globset::GlobSet;
+
+use crate::filters::build_glob_set;
+use crate::git::FileChange;
+
+/// Turns line counts into a review-cost score.
+///
+/// Each line is weighted by whether it was inserted or deleted, then by the
+/// most specific (longest) glob matching its path. Paths that match no glob
+/// have a weight of 1.
+#[derive(Debug, Clone)]
+pub struct Weights {
+    insertion: f64,
+    deletion: f64,
+    paths: Vec<(String, GlobSet, f64)>,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            insertion: 1.0,
+            deletion: 1.0,
+            paths: Vec::new(),
+        }
+    }
+}
+
+impl Weights {
+    pub fn new(insertion: f64, deletion: f64, paths: &HashMap<String, f64>) -> Self {
+        let mut paths: Vec<(String, GlobSet, f64)> = paths
+            .iter()
+            .filter_map(|(pattern, weight)| {
+                let glob_set = build_glob_set(std::slice::from_ref(pattern))?;
+                Some((pattern.clone(), glob_set, *weight))
+            })
+            .collect();
+        paths.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+
+        Self {
+            insertion,
+            deletion,
+            paths,
+        }
+    }
+
+    pub fn path_weight(&self, path: &Path) -> f64 {
+        self.paths
+            .iter()
+            .find(|(_, glob_set, _)| glob_set.is_match(path))
+            .map_or(1.0, |(_, _, weight)| *weight)
+    }
+
+    pub fn score(&self, file: &FileChange) -> f64 {
+        let lines =
+            f64::from(file.insertions) * self.insertion + f64::from(file.deletions) * self.deletion;
+        lines * self.path_weight(&file.path)
+    }
+
+    /// Scores the files that aren't skipped.
+    pub fn score_files(&self, files: &[FileChange]) -> f64 {
+        // Folded from 0.0, as summing no floats gives -0.0, shown as "-0"
+        files
+            .iter()
+            .filter(|file| file.skipped.is_none())
+            .fold(0.0, |score, file| score + self.score(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::FileStatus;
+
+    fn weights() -> Weights {
+        let paths = HashMap::from([
+            ("tests/**".to_string(), 0.5),
+            ("*.md".to_string(), 0.25),
+            ("tests/fixtures/**".to_string(), 0.0),
+        ]);
+        Weights::new(1.0, 0.5, &paths)
+    }
+
+    #[test]
+    fn the_most_specific_glob_wins() {
+        let weights = weights();
+
+        assert_eq!(weights.path_weight(Path::new("src/main.rs")), 1.0);
+        assert_eq!(weights.path_weight(Path::new("docs/guide.md")), 0.25);
+        assert_eq!(weights.path_weight(Path::new("tests/cli.rs")), 0.5);
+        assert_eq!(weights.path_weight(Path::new("tests/fixtures/a.json")), 0.0);
+    }
+
+    #[test]
+    fn insertions_and_deletions_are_weighted_separately() {
+        let weights = weights();
+        let files = vec![
+            FileChange::new("src/main.rs", 10, 10, FileStatus::Modified),
+            FileChange::new("tests/cli.rs", 20, 0, FileStatus::Modified),
+        ];
+
+        assert_eq!(weights.score_files(&files), 25.0);
+    }
+
+    #[test]
+    fn nothing_to_score_is_zero() {
+        let score = weights().score_files(&[]);
+
+        assert_eq!(format!("{score:.0}"), "0");
+    }
+}
@@ -0,0 +1,364 @@
+use chrono::{DateTime, Duration, Local};
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::Frame;
+
+use crate::commands::{self, CommandResult};
+use crate::git::snapshot::{self, Snapshot};
+use crate::git::{CommandOutcome, GitCommand};
+use crate::reminders::format_age;
+use crate::{GitState, Options};
+
+/// When to record the dirty worktree under `refs/diffding/snapshots`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnapshotSettings {
+    pub enabled: bool,
+    /// Take one once the latest is this old and the worktree is dirty
+    pub interval: Option<Duration>,
+    /// Take one whenever the bell starts ringing
+    pub on_bell: bool,
+}
+
+impl SnapshotSettings {
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            enabled: options.snapshots,
+            interval: options.snapshot_interval.map(Duration::minutes),
+            on_bell: options.snapshot_on_bell,
+        }
+    }
+
+    /// Whether the interval has passed since the latest snapshot.
+    pub fn is_due(&self, git_state: &GitState, now: DateTime<Local>) -> bool {
+        let Some(interval) = self.interval.filter(|_| self.enabled) else {
+            return false;
+        };
+        let Some(dirty_since) = git_state.dirty_since else {
+            return false;
+        };
+        let latest = git_state
+            .snapshots
+            .first()
+            .map_or(dirty_since, |snapshot| snapshot.time.max(dirty_since));
+        now.timestamp() - latest >= interval.num_seconds()
+    }
+}
+
+/// `diffding snapshots [list | diff <name> | restore <name>]`
+pub fn command(options: &Options, args: &[String]) -> CommandResult<i32> {
+    let (_, workdir) = commands::repository(options)?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [] | ["list"] => {
+            let now = Local::now();
+            for snapshot in snapshot::list(&workdir)? {
+                println!(
+                    "{}  {}  {} ago",
+                    snapshot.name,
+                    snapshot.commit,
+                    format_age(snapshot.time, now)
+                );
+            }
+            Ok(0)
+        }
+        ["diff", name] => {
+            print!("{}", snapshot::diff(&workdir, name)?);
+            Ok(0)
+        }
+        ["restore", name] => {
+            let outcome = snapshot::restore(&workdir, name)?;
+            println!("{}", outcome.output);
+            Ok(if outcome.success { 0 } else { 1 })
+        }
+        _ => Err("usage: diffding snapshots [list | diff <name> | restore <name>]".into()),
+    }
+}
+
+/// What the manager should do after a key press in the pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotAction {
+    None,
+    Run(GitCommand),
+    Close,
+}
+
+/// A snapshot's diff, shown in place of the list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiffView {
+    name: String,
+    /// `None` until the backend has read it
+    diff: Option<String>,
+    scroll: u16,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotPane {
+    selected: usize,
+    diff_view: Option<DiffView>,
+    /// Set by the first `r`; the second one restores
+    confirm_restore: Option<String>,
+    /// How the last restore went
+    pub message: Option<CommandOutcome>,
+}
+
+impl SnapshotPane {
+    pub fn handle_key(&mut self, key: KeyEvent, snapshots: &[Snapshot]) -> SnapshotAction {
+        if let Some(diff_view) = self.diff_view.as_mut() {
+            match key.code {
+                KeyCode::Esc => self.diff_view = None,
+                KeyCode::Up | KeyCode::Char('k') => {
+                    diff_view.scroll = diff_view.scroll.saturating_sub(1)
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    diff_view.scroll = diff_view.scroll.saturating_add(1)
+                }
+                KeyCode::PageUp => diff_view.scroll = diff_view.scroll.saturating_sub(20),
+                KeyCode::PageDown => diff_view.scroll = diff_view.scroll.saturating_add(20),
+                _ => {}
+            }
+            return SnapshotAction::None;
+        }
+
+        self.selected = self.selected.min(snapshots.len().saturating_sub(1));
+        let snapshot = snapshots.get(self.selected);
+        if key.code != KeyCode::Char('r') {
+            self.confirm_restore = None;
+        }
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('w') => SnapshotAction::Close,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                SnapshotAction::None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < snapshots.len() {
+                    self.selected += 1;
+                }
+                SnapshotAction::None
+            }
+            KeyCode::Char('n') => SnapshotAction::Run(GitCommand::TakeSnapshot),
+            KeyCode::Enter => match snapshot {
+                Some(snapshot) => {
+                    self.diff_view = Some(DiffView {
+                        name: snapshot.name.clone(),
+                        diff: None,
+                        scroll: 0,
+                    });
+                    SnapshotAction::Run(GitCommand::LoadSnapshotDiff {
+                        name: snapshot.name.clone(),
+                    })
+                }
+                None => SnapshotAction::None,
+            },
+            KeyCode::Char('r') => match snapshot {
+                Some(snapshot) if self.confirm_restore.as_ref() == Some(&snapshot.name) => {
+                    self.confirm_restore = None;
+                    SnapshotAction::Run(GitCommand::RestoreSnapshot {
+                        name: snapshot.name.clone(),
+                    })
+                }
+                Some(snapshot) => {
+                    self.confirm_restore = Some(snapshot.name.clone());
+                    SnapshotAction::None
+                }
+                None => SnapshotAction::None,
+            },
+            _ => SnapshotAction::None,
+        }
+    }
+
+    /// Shows the diff the backend read, if it's still wanted.
+    pub fn set_diff(&mut self, name: &str, diff: String) {
+        if let Some(diff_view) = self.diff_view.as_mut() {
+            if diff_view.name == name {
+                diff_view.diff = Some(diff);
+            }
+        }
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, area: Rect, pane: &SnapshotPane, git_state: &GitState) {
+    let message_height = if pane.message.is_some() || pane.confirm_restore.is_some() {
+        1
+    } else {
+        0
+    };
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(message_height),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    if let Some(name) = &pane.confirm_restore {
+        let confirm = format!("Press r again to apply {name} to the worktree");
+        f.render_widget(
+            Paragraph::new(confirm).style(Style::default().fg(Color::LightYellow)),
+            split[0],
+        );
+    } else if let Some(outcome) = &pane.message {
+        let color = if outcome.success {
+            Color::LightGreen
+        } else {
+            Color::LightRed
+        };
+        let message = outcome.output.lines().last().unwrap_or("Restored");
+        f.render_widget(
+            Paragraph::new(message).style(Style::default().fg(color)),
+            split[0],
+        );
+    }
+
+    let help = match &pane.diff_view {
+        Some(diff_view) => {
+            draw_diff(f, split[1], diff_view);
+            "<up>/<down> scroll / <esc> back"
+        }
+        None => {
+            draw_list(f, split[1], pane, &git_state.snapshots);
+            "<enter> diff / <r> restore / <n> snapshot now / <esc> close"
+        }
+    };
+    f.render_widget(
+        Paragraph::new(help).style(Style::default().fg(Color::LightYellow)),
+        split[2],
+    );
+}
+
+fn draw_list<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    pane: &SnapshotPane,
+    snapshots: &[Snapshot],
+) {
+    let now = Local::now();
+    let items: Vec<ListItem> = snapshots
+        .iter()
+        .map(|snapshot| {
+            ListItem::new(Spans::from(vec![
+                Span::raw(snapshot.name.clone()),
+                Span::styled(
+                    format!("  {}", snapshot.commit),
+                    Style::default().fg(Color::LightYellow),
+                ),
+                Span::styled(
+                    format!("  {} ago", format_age(snapshot.time, now)),
+                    Style::default().fg(Color::Gray),
+                ),
+            ]))
+        })
+        .collect();
+
+    let title = if snapshots.is_empty() {
+        " SNAPSHOTS (none yet) "
+    } else {
+        " SNAPSHOTS "
+    };
+    let mut state = ListState::default();
+    if !snapshots.is_empty() {
+        state.select(Some(pane.selected.min(snapshots.len() - 1)));
+    }
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().bg(Color::Indexed(237)))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_diff<B: Backend>(f: &mut Frame<B>, area: Rect, diff_view: &DiffView) {
+    let lines: Vec<Spans> = match &diff_view.diff {
+        None => vec![Spans::from("Reading the diff...")],
+        Some(diff) => diff
+            .lines()
+            .map(|line| {
+                let color = match line.as_bytes().first() {
+                    Some(b'@') => Color::LightCyan,
+                    Some(b'+') => Color::LightGreen,
+                    Some(b'-') => Color::LightRed,
+                    _ => Color::Gray,
+                };
+                Spans::from(Span::styled(line.to_string(), Style::default().fg(color)))
+            })
+            .collect(),
+    };
+    let title = format!(" {} ", diff_view.name);
+    let diff = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .scroll((diff_view.scroll, 0));
+    f.render_widget(diff, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(pane: &mut SnapshotPane, code: KeyCode, snapshots: &[Snapshot]) -> SnapshotAction {
+        pane.handle_key(KeyEvent::new(code, KeyModifiers::NONE), snapshots)
+    }
+
+    fn snapshot(name: &str, time: i64) -> Snapshot {
+        Snapshot {
+            name: name.to_string(),
+            commit: "abc1234".to_string(),
+            time,
+        }
+    }
+
+    #[test]
+    fn due_once_the_interval_has_passed_since_the_latest_snapshot() {
+        let settings = SnapshotSettings {
+            enabled: true,
+            interval: Some(Duration::minutes(30)),
+            on_bell: false,
+        };
+        let now = Local::now();
+        let mut git_state = GitState::new(100);
+        assert!(!settings.is_due(&git_state, now));
+
+        git_state.dirty_since = Some(now.timestamp() - 3600);
+        assert!(settings.is_due(&git_state, now));
+
+        git_state.snapshots = vec![snapshot("recent", now.timestamp() - 600)];
+        assert!(!settings.is_due(&git_state, now));
+    }
+
+    #[test]
+    fn restoring_takes_two_presses() {
+        let snapshots = [
+            snapshot("20240131T174502", 0),
+            snapshot("20240131T164502", 0),
+        ];
+        let mut pane = SnapshotPane::default();
+
+        press(&mut pane, KeyCode::Down, &snapshots);
+        assert_eq!(
+            press(&mut pane, KeyCode::Char('r'), &snapshots),
+            SnapshotAction::None
+        );
+        assert_eq!(
+            press(&mut pane, KeyCode::Char('r'), &snapshots),
+            SnapshotAction::Run(GitCommand::RestoreSnapshot {
+                name: "20240131T164502".to_string()
+            })
+        );
+
+        press(&mut pane, KeyCode::Char('r'), &snapshots);
+        press(&mut pane, KeyCode::Up, &snapshots);
+        assert_eq!(
+            press(&mut pane, KeyCode::Char('r'), &snapshots),
+            SnapshotAction::None
+        );
+    }
+}
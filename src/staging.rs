@@ -0,0 +1,476 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::Frame;
+
+use crate::git::{DiffStat, FileChange, FilePatch, GitCommand};
+use crate::GitState;
+
+/// A changed file as the staging pane shows it: what's staged and what
+/// isn't, side by side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagingEntry {
+    /// The change to act on: the unstaged one when there is one
+    pub file: FileChange,
+    pub staged: Option<DiffStat>,
+    pub unstaged: Option<DiffStat>,
+}
+
+impl StagingEntry {
+    fn has_unstaged(&self) -> bool {
+        self.unstaged.is_some()
+    }
+}
+
+/// Every changed file, staged or not, sorted by path.
+pub fn entries(git_state: &GitState) -> Vec<StagingEntry> {
+    let files = &git_state.changed_files;
+    let mut entries: BTreeMap<PathBuf, StagingEntry> = BTreeMap::new();
+    for file in &files.staged {
+        entries.insert(
+            file.path.clone(),
+            StagingEntry {
+                file: file.clone(),
+                staged: Some(DiffStat::new(file.insertions, file.deletions)),
+                unstaged: None,
+            },
+        );
+    }
+    for file in files.unstaged.iter().chain(&files.untracked) {
+        let unstaged = Some(DiffStat::new(file.insertions, file.deletions));
+        entries
+            .entry(file.path.clone())
+            .and_modify(|entry| {
+                entry.file = file.clone();
+                entry.unstaged = unstaged;
+            })
+            .or_insert_with(|| StagingEntry {
+                file: file.clone(),
+                staged: None,
+                unstaged,
+            });
+    }
+    entries.into_values().collect()
+}
+
+/// What the manager should do after a key press in the pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StagingAction {
+    None,
+    Run(GitCommand),
+    Commit,
+    Close,
+}
+
+/// One file's hunks, from one side of the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HunkView {
+    file: FileChange,
+    /// Showing the staged hunks rather than the unstaged ones
+    staged: bool,
+    /// `None` until the backend has read the diff
+    patch: Option<FilePatch>,
+    selected: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StagingPane {
+    selected: usize,
+    hunk_view: Option<HunkView>,
+    /// Output of the last command that failed
+    pub error: Option<String>,
+}
+
+impl StagingPane {
+    pub fn handle_key(&mut self, key: KeyEvent, entries: &[StagingEntry]) -> StagingAction {
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+        if let Some(hunk_view) = self.hunk_view.as_mut() {
+            if key.code == KeyCode::Esc {
+                self.hunk_view = None;
+                return StagingAction::None;
+            }
+            return hunk_view.handle_key(key);
+        }
+        let entry = entries.get(self.selected);
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('s') => StagingAction::Close,
+            KeyCode::Char('c') => StagingAction::Commit,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                StagingAction::None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < entries.len() {
+                    self.selected += 1;
+                }
+                StagingAction::None
+            }
+            // Stages whatever isn't staged yet, otherwise unstages
+            KeyCode::Char(' ') => match entry {
+                Some(entry) if entry.has_unstaged() => StagingAction::Run(GitCommand::Stage {
+                    file: entry.file.clone(),
+                }),
+                Some(entry) => StagingAction::Run(GitCommand::Unstage {
+                    file: entry.file.clone(),
+                }),
+                None => StagingAction::None,
+            },
+            KeyCode::Char('u') => match entry {
+                Some(entry) if entry.staged.is_some() => StagingAction::Run(GitCommand::Unstage {
+                    file: entry.file.clone(),
+                }),
+                _ => StagingAction::None,
+            },
+            KeyCode::Enter => match entry {
+                Some(entry) => {
+                    let hunk_view = HunkView {
+                        file: entry.file.clone(),
+                        staged: !entry.has_unstaged(),
+                        patch: None,
+                        selected: 0,
+                    };
+                    let load = hunk_view.load();
+                    self.hunk_view = Some(hunk_view);
+                    StagingAction::Run(load)
+                }
+                None => StagingAction::None,
+            },
+            _ => StagingAction::None,
+        }
+    }
+
+    /// Rereads the open file's hunks, e.g. after one was staged.
+    pub fn reload(&self) -> Option<GitCommand> {
+        self.hunk_view.as_ref().map(HunkView::load)
+    }
+
+    /// Shows the hunks the backend read, if they're still wanted, or why
+    /// they couldn't be read.
+    pub fn set_patch(&mut self, path: &Path, staged: bool, patch: Result<FilePatch, String>) {
+        if let Some(hunk_view) = self.hunk_view.as_mut() {
+            if hunk_view.file.path == *path && hunk_view.staged == staged {
+                let patch = patch.unwrap_or_else(|error| {
+                    self.error = Some(error);
+                    FilePatch::default()
+                });
+                hunk_view.selected = hunk_view.selected.min(patch.hunks.len().saturating_sub(1));
+                hunk_view.patch = Some(patch);
+            }
+        }
+    }
+}
+
+impl HunkView {
+    fn load(&self) -> GitCommand {
+        GitCommand::LoadPatch {
+            file: self.file.clone(),
+            staged: self.staged,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> StagingAction {
+        let hunk_count = self.patch.as_ref().map_or(0, |patch| patch.hunks.len());
+
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                StagingAction::None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < hunk_count {
+                    self.selected += 1;
+                }
+                StagingAction::None
+            }
+            // Switches between the unstaged and the staged hunks
+            KeyCode::Tab => {
+                self.staged = !self.staged;
+                self.patch = None;
+                self.selected = 0;
+                StagingAction::Run(self.load())
+            }
+            KeyCode::Char(' ') if self.selected < hunk_count => match &self.patch {
+                Some(patch) => StagingAction::Run(GitCommand::ApplyHunk {
+                    file: self.file.clone(),
+                    patch: patch.clone(),
+                    hunk: self.selected,
+                    staged: self.staged,
+                }),
+                None => StagingAction::None,
+            },
+            _ => StagingAction::None,
+        }
+    }
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, area: Rect, pane: &StagingPane, git_state: &GitState) {
+    let split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(1),
+                Constraint::Length(if pane.error.is_some() { 1 } else { 0 }),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    f.render_widget(Paragraph::new(totals(git_state)), split[0]);
+    if let Some(error) = &pane.error {
+        let error = error.lines().last().unwrap_or_default();
+        f.render_widget(
+            Paragraph::new(error).style(Style::default().fg(Color::LightRed)),
+            split[1],
+        );
+    }
+
+    let help = match &pane.hunk_view {
+        Some(hunk_view) => {
+            draw_hunks(f, split[2], hunk_view);
+            if hunk_view.staged {
+                "<space> unstage hunk / <tab> unstaged hunks / <esc> back"
+            } else {
+                "<space> stage hunk / <tab> staged hunks / <esc> back"
+            }
+        }
+        None => {
+            draw_files(f, split[2], pane, &entries(git_state));
+            "<space> stage or unstage / <u> unstage / <enter> hunks / <c> commit / <esc> close"
+        }
+    };
+    f.render_widget(
+        Paragraph::new(help).style(Style::default().fg(Color::LightYellow)),
+        split[3],
+    );
+}
+
+/// Staged and unstaged totals, with the staged total held against the
+/// threshold so a commit can be built to fit under it.
+fn totals(git_state: &GitState) -> Spans<'static> {
+    let changes = &git_state.git_changes;
+    let staged_color = if changes.staged.total > git_state.threshold {
+        Color::LightRed
+    } else {
+        Color::LightGreen
+    };
+    Spans::from(vec![
+        Span::raw("Staged "),
+        Span::styled(
+            format!("{}/{}", changes.staged.total, git_state.threshold),
+            Style::default()
+                .fg(staged_color)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!(
+            "  Unstaged {}  Untracked {}",
+            changes.unstaged.total, changes.untracked
+        )),
+    ])
+}
+
+fn stat_spans(stat: Option<DiffStat>) -> Vec<Span<'static>> {
+    match stat {
+        Some(stat) => vec![
+            Span::styled(
+                format!("{:>6}", format!("+{}", stat.insertions)),
+                Style::default().fg(Color::LightGreen),
+            ),
+            Span::styled(
+                format!("{:>6}", format!("-{}", stat.deletions)),
+                Style::default().fg(Color::LightRed),
+            ),
+        ],
+        None => vec![Span::raw(format!("{:>12}", ""))],
+    }
+}
+
+fn draw_files<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    pane: &StagingPane,
+    entries: &[StagingEntry],
+) {
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|entry| {
+            let mut spans = vec![Span::styled(
+                format!("{} ", entry.file.status.code()),
+                Style::default().fg(Color::Gray),
+            )];
+            spans.extend(stat_spans(entry.staged));
+            spans.push(Span::raw("  "));
+            spans.extend(stat_spans(entry.unstaged));
+            spans.push(Span::raw(format!("  {}", entry.file.path.display())));
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    if !entries.is_empty() {
+        state.select(Some(pane.selected.min(entries.len() - 1)));
+    }
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" STAGING     staged    unstaged "),
+        )
+        .highlight_style(Style::default().bg(Color::Indexed(237)))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_hunks<B: Backend>(f: &mut Frame<B>, area: Rect, hunk_view: &HunkView) {
+    let side = if hunk_view.staged {
+        "staged"
+    } else {
+        "unstaged"
+    };
+    let title = format!(" {} ({side} hunks) ", hunk_view.file.path.display());
+    let block = Block::default().borders(Borders::ALL).title(title);
+
+    let message = match &hunk_view.patch {
+        None => Some("Reading the diff..."),
+        Some(patch) if patch.hunks.is_empty() => Some("No hunks to show"),
+        Some(_) => None,
+    };
+    if let Some(message) = message {
+        f.render_widget(Paragraph::new(message).block(block), area);
+        return;
+    }
+
+    let hunks = hunk_view
+        .patch
+        .as_ref()
+        .map_or(&[][..], |patch| &patch.hunks);
+    let items: Vec<ListItem> = hunks
+        .iter()
+        .map(|hunk| {
+            let (insertions, deletions) = hunk.stats();
+            let summary = Spans::from(Span::styled(
+                format!("+{insertions} -{deletions}"),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+            let lines: Vec<Spans> = std::iter::once(summary)
+                .chain(hunk.lines.iter().map(|line| {
+                    let color = match line.as_bytes().first() {
+                        Some(b'@') => Color::LightCyan,
+                        Some(b'+') => Color::LightGreen,
+                        Some(b'-') => Color::LightRed,
+                        _ => Color::Gray,
+                    };
+                    // CRLF files keep their `\r`, which the terminal would act on
+                    let line = line.trim_end_matches('\r').to_string();
+                    Spans::from(Span::styled(line, Style::default().fg(color)))
+                }))
+                .collect();
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    state.select(Some(hunk_view.selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Indexed(237)))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{ChangedFiles, FileStatus};
+    use crossterm::event::KeyModifiers;
+
+    fn press(pane: &mut StagingPane, code: KeyCode, entries: &[StagingEntry]) -> StagingAction {
+        pane.handle_key(KeyEvent::new(code, KeyModifiers::NONE), entries)
+    }
+
+    fn partly_staged_state() -> GitState {
+        let mut git_state = GitState::new(100);
+        git_state.changed_files = ChangedFiles {
+            staged: vec![
+                FileChange::new("a.rs", 3, 1, FileStatus::Modified),
+                FileChange::new("b.rs", 2, 0, FileStatus::Modified),
+            ],
+            unstaged: vec![FileChange::new("a.rs", 5, 0, FileStatus::Modified)],
+            combined: vec![],
+            untracked: vec![FileChange::new("new.rs", 9, 0, FileStatus::Untracked)],
+        };
+        git_state
+    }
+
+    #[test]
+    fn entries_pair_staged_and_unstaged_changes() {
+        let entries = entries(&partly_staged_state());
+
+        let paths: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.file.path.clone())
+            .collect();
+        assert_eq!(paths, ["a.rs", "b.rs", "new.rs"].map(PathBuf::from));
+        assert_eq!(entries[0].staged, Some(DiffStat::new(3, 1)));
+        assert_eq!(entries[0].unstaged, Some(DiffStat::new(5, 0)));
+        assert_eq!(entries[1].unstaged, None);
+    }
+
+    #[test]
+    fn space_stages_the_rest_or_unstages_a_fully_staged_file() {
+        let entries = entries(&partly_staged_state());
+        let mut pane = StagingPane::default();
+
+        assert_eq!(
+            press(&mut pane, KeyCode::Char(' '), &entries),
+            StagingAction::Run(GitCommand::Stage {
+                file: entries[0].file.clone()
+            })
+        );
+        press(&mut pane, KeyCode::Down, &entries);
+        assert_eq!(
+            press(&mut pane, KeyCode::Char(' '), &entries),
+            StagingAction::Run(GitCommand::Unstage {
+                file: entries[1].file.clone()
+            })
+        );
+    }
+
+    #[test]
+    fn hunk_view_stages_the_selected_hunk() {
+        let entries = entries(&partly_staged_state());
+        let mut pane = StagingPane::default();
+
+        let load = press(&mut pane, KeyCode::Enter, &entries);
+        assert_eq!(load, StagingAction::Run(pane.reload().unwrap()));
+
+        let patch = FilePatch::parse(
+            "diff --git a/a.rs b/a.rs\n@@ -1 +1,2 @@\n x\n+y\n@@ -9 +10,2 @@\n x\n+z\n",
+        );
+        pane.set_patch(Path::new("a.rs"), false, Ok(patch.clone()));
+        press(&mut pane, KeyCode::Down, &entries);
+
+        assert_eq!(
+            press(&mut pane, KeyCode::Char(' '), &entries),
+            StagingAction::Run(GitCommand::ApplyHunk {
+                file: entries[0].file.clone(),
+                patch,
+                hunk: 1,
+                staged: false,
+            })
+        );
+        assert_eq!(
+            press(&mut pane, KeyCode::Esc, &entries),
+            StagingAction::None
+        );
+        assert_eq!(pane.reload(), None);
+    }
+}
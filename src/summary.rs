@@ -1,11 +1,15 @@
 use std::sync::Arc;
 
+use chrono::Local;
 use tui::backend::Backend;
 use tui::layout::{Constraint, Rect};
 use tui::style::{Color, Modifier, Style};
 use tui::widgets::{Block, Borders, Cell, Row, Table, TableState};
 use tui::Frame;
 
+use crate::git::{ByteStat, DiffStat};
+use crate::reminders::{format_age, format_bytes};
+use crate::watcher::RefreshMode;
 use crate::{GitState, Options};
 
 pub fn summary<B: Backend>(
@@ -15,21 +19,80 @@ pub fn summary<B: Backend>(
     options: Arc<Options>,
 ) {
     let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-    let insertions = &git_state.git_changes.insertions.to_string();
-    let deletions = &git_state.git_changes.deletions.to_string();
+    let changes = &git_state.git_changes;
+    let staged = &diff_stat_string(&changes.staged);
+    let unstaged = &diff_stat_string(&changes.unstaged);
+    let combined = &diff_stat_string(&changes.combined);
+    let untracked = &format!("+{}", changes.untracked);
+    let skipped = &changes.skipped.to_string();
+    let binary = &byte_stat_string(&changes.binary);
     let threshold = &git_state.threshold.to_string();
-    let loop_time = &options.git_update_time.to_string();
-    let total = &git_state.git_changes.total.to_string();
-    let total_string = &format!("{total} / {threshold}");
-    let loop_time_string = &format!("{loop_time}ms");
-    let items = vec![
+    let refresh = &match git_state.refresh_mode {
+        RefreshMode::Watching => format!("watching ({}s)", options.safety_interval / 1000),
+        RefreshMode::Polling => format!("polling ({}ms)", options.git_update_time),
+        RefreshMode::WatchLimit => format!(
+            "polling ({}ms), out of file watches",
+            options.git_update_time
+        ),
+    };
+    let total = &match changes.total_with_whitespace {
+        Some(with_whitespace) if with_whitespace != changes.total => {
+            format!("{} ({with_whitespace} raw)", changes.total)
+        }
+        _ => changes.total.to_string(),
+    };
+    let score_string = &format!("{:.1} / {threshold}", changes.score);
+    let count_mode = &git_state.count_mode.to_string();
+    let backend = git_state.backend;
+    let vcs = git_state.vcs;
+    let revision = &format!(
+        "{} {}",
+        vcs.revision_label(),
+        git_state.current_commit_short
+    );
+    let branch = if git_state.branch.is_detached() {
+        vcs.no_branch_label()
+    } else {
+        git_state.branch.name.as_deref().unwrap_or_default()
+    };
+    let branch_size_label = &git_state.branch_size.as_ref().map_or_else(
+        || "Branch size".to_string(),
+        |size| format!("Since {}", size.base),
+    );
+    let branch_size = &match &git_state.branch_size {
+        Some(size) => format!(
+            "{} over {}",
+            diff_stat_string(&size.changes),
+            format_age(size.since, Local::now())
+        ),
+        None => git_state
+            .branch_size_error
+            .clone()
+            .unwrap_or_else(|| "n/a".to_string()),
+    };
+    let upstream = &match &git_state.branch.upstream {
+        Some(upstream) => format!("{} +{} -{}", upstream.name, upstream.ahead, upstream.behind),
+        None => "none".to_string(),
+    };
+    let items = [
         vec!["", ""],
-        vec!["Insertions", insertions],
-        vec!["Deletions", deletions],
+        vec!["Staged", staged],
+        vec!["Unstaged", unstaged],
+        vec!["Combined", combined],
+        vec!["Untracked", untracked],
+        vec!["Skipped", skipped],
+        vec!["Binary", binary],
         vec!["----------", "-----------------"],
-        vec!["Total", total_string],
+        vec!["Total", total],
+        vec!["Score", score_string],
+        vec!["Counting", count_mode],
         vec!["", ""],
-        vec!["Loop Time", loop_time_string],
+        vec!["Refresh", refresh],
+        vec!["Backend", backend],
+        vec![vcs.revision_noun(), revision],
+        vec![vcs.branch_noun(), branch],
+        vec!["Upstream", upstream],
+        vec![branch_size_label, branch_size],
     ];
     let rows = items.iter().map(|item| {
         let height = item
@@ -53,3 +116,14 @@ pub fn summary<B: Backend>(
         .widths(&[Constraint::Min(20), Constraint::Min(20)]);
     f.render_stateful_widget(t, area, &mut TableState::default());
 }
+
+fn diff_stat_string(diff_stat: &DiffStat) -> String {
+    format!("+{} / -{}", diff_stat.insertions, diff_stat.deletions)
+}
+
+fn byte_stat_string(byte_stat: &ByteStat) -> String {
+    match byte_stat.files {
+        1 => format!("1 file, {}", format_bytes(byte_stat.bytes)),
+        files => format!("{files} files, {}", format_bytes(byte_stat.bytes)),
+    }
+}
@@ -228,7 +228,7 @@ fn blue_gradient(ratio: f64, threshold_ratio: f64, max_ratio: f64) -> u8 {
     0
 }
 
-pub fn get_gauge_color<'a>(ratio: f64, threshold_ratio: f64, max_ratio: f64) -> Color {
+pub fn get_gauge_color(ratio: f64, threshold_ratio: f64, max_ratio: f64) -> Color {
     // let r: u8 = 0;
     let r = red_gradient(ratio, threshold_ratio, max_ratio);
     // let g: u8 = 0;
@@ -17,28 +17,29 @@ use tui::{
     Frame, Terminal,
 };
 
+use crate::git::{BranchState, RepoStatus, Vcs};
 use crate::manager::AppState;
+use crate::operations::Operation;
+use crate::reminders::{format_age, format_bytes};
 use crate::threshold_gauge::ThresholdGauge;
 use crate::{GitState, Options};
 
 #[derive(Debug)]
 pub enum UiMessage {
     GitUpdate {
-        git_state: GitState,
+        /// One state per repository, in the order of `Options::repos`
+        git_states: Vec<GitState>,
         app_state: Arc<Mutex<AppState>>,
     },
 }
 
-pub async fn ui_loop<'ui>(mut rx: tokio::sync::mpsc::Receiver<UiMessage>, options: Arc<Options>) {
+pub async fn ui_loop(mut rx: tokio::sync::mpsc::Receiver<UiMessage>, options: Arc<Options>) {
     enable_raw_mode().unwrap();
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen).unwrap();
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend).unwrap();
 
-    let threshold = f64::from(options.threshold);
-    let max_value = threshold * 1.5;
-
     let wide_width = 100;
 
     while let Some(ui_message) = rx.recv().await {
@@ -46,16 +47,14 @@ pub async fn ui_loop<'ui>(mut rx: tokio::sync::mpsc::Receiver<UiMessage>, option
 
         match ui_message {
             GitUpdate {
-                git_state,
+                git_states,
                 app_state,
             } => {
                 draw_ui(
                     options.clone(),
                     &mut terminal,
-                    threshold,
-                    max_value,
                     wide_width,
-                    git_state,
+                    git_states,
                     app_state,
                 );
             }
@@ -66,14 +65,24 @@ pub async fn ui_loop<'ui>(mut rx: tokio::sync::mpsc::Receiver<UiMessage>, option
 fn draw_ui(
     options: Arc<Options>,
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    threshold: f64,
-    max_value: f64,
     wide_width: u16,
-    git_state: GitState,
+    git_states: Vec<GitState>,
     app_state: Arc<Mutex<AppState>>,
 ) {
-    let git_state_draw = git_state.clone();
-    let title = git_summary(git_state);
+    let Some(last_repo) = git_states.len().checked_sub(1) else {
+        return;
+    };
+    let (selected_repo, commit_dialog, staging_pane, snapshot_pane) = {
+        let app_state = app_state.lock().unwrap();
+        (
+            app_state.selected_repo.min(last_repo),
+            app_state.commit_dialog.clone(),
+            app_state.staging_pane.clone(),
+            app_state.snapshot_pane.clone(),
+        )
+    };
+    let git_state_draw = git_states[selected_repo].clone();
+    let is_multi_repo = git_states.len() > 1;
 
     terminal
         .draw(|f| {
@@ -89,7 +98,8 @@ fn draw_ui(
                 .constraints(
                     [
                         Constraint::Length(3),
-                        Constraint::Length(3),
+                        Constraint::Length(if is_multi_repo { 2 } else { 0 }),
+                        Constraint::Length(3 * git_states.len() as u16),
                         Constraint::Length(2),
                         Constraint::Min(0),
                         Constraint::Length(1),
@@ -99,46 +109,95 @@ fn draw_ui(
                 .split(f.size());
 
             let app_title_area = top_split[0];
-            let bar_area = top_split[1];
-            let data_display_area = top_split[3];
-            let footer_area = top_split[4];
+            let aggregate_area = top_split[1];
+            let bars_area = top_split[2];
+            let status_line_area = top_split[3];
+            let data_display_area = top_split[4];
+            let footer_area = top_split[5];
 
             let is_wide = f.size().width > wide_width;
 
-            let data_display = get_data_display(data_display_area, is_wide);
+            let data_split = Layout::default()
+                .direction(tui::layout::Direction::Vertical)
+                .constraints(
+                    [
+                        Constraint::Length(if is_wide { 18 } else { 25 }),
+                        Constraint::Min(0),
+                    ]
+                    .as_ref(),
+                )
+                .split(data_display_area);
+            let data_display = get_data_display(data_split[0], is_wide);
+            let files_area = Layout::default()
+                .horizontal_margin(1)
+                .constraints([Constraint::Min(0)].as_ref())
+                .split(data_split[1])[0];
+
+            draw_app_title(f, app_title_area, &git_state_draw);
 
-            draw_app_title(f, app_title_area);
+            if is_multi_repo {
+                draw_aggregate(f, aggregate_area, &git_states);
+            }
 
-            draw_bar(
-                threshold,
-                max_value,
-                git_state_draw.git_changes.total.clone(),
-                title,
-                f,
-                bar_area,
-            );
+            let bar_areas = Layout::default()
+                .direction(tui::layout::Direction::Vertical)
+                .constraints(vec![Constraint::Length(3); git_states.len()])
+                .split(bars_area);
+            for (repo, (git_state, bar_area)) in git_states.iter().zip(bar_areas).enumerate() {
+                let threshold = f64::from(git_state.threshold);
+                let label = is_multi_repo.then(|| repo_label(git_state, repo == selected_repo));
+                draw_bar(
+                    threshold,
+                    threshold * 1.5,
+                    git_state.git_changes.score,
+                    git_summary(git_state.clone(), label),
+                    f,
+                    bar_area,
+                );
+            }
 
-            let options_summary = options.clone();
-            crate::summary::summary(f, data_display[2], &git_state_draw, options_summary);
+            draw_status_line(f, status_line_area, &git_state_draw);
 
-            big_text(f, data_display[0], &git_state_draw);
+            if git_state_draw.repo_status == RepoStatus::Ready {
+                let options_summary = options.clone();
+                crate::summary::summary(f, data_display[2], &git_state_draw, options_summary);
 
-            draw_footer(f, footer_area, app_state.clone(), options.snooze_length);
+                big_text(f, data_display[0], &git_state_draw);
+
+                match (&staging_pane, &snapshot_pane) {
+                    (Some(pane), _) => crate::staging::draw(f, files_area, pane, &git_state_draw),
+                    (_, Some(pane)) => crate::snapshots::draw(f, files_area, pane, &git_state_draw),
+                    _ => crate::file_list::file_list(f, files_area, &git_state_draw),
+                }
+            } else {
+                draw_repo_status(f, data_display_area, &git_state_draw);
+            }
+
+            draw_footer(
+                f,
+                footer_area,
+                app_state.clone(),
+                options.snooze_length,
+                is_multi_repo,
+                options.snapshots,
+            );
+
+            if let Some(dialog) = &commit_dialog {
+                crate::commit_dialog::draw(f, f.size(), dialog, &git_state_draw);
+            }
 
             // debug_info(f, is_wide);
         })
         .unwrap();
 }
 
-fn is_snoozed(app_state: Arc<Mutex<AppState>>) -> bool {
-    app_state.lock().unwrap().snoozed
-}
-
 fn draw_footer(
     f: &mut Frame<CrosstermBackend<Stdout>>,
     footer_area: Rect,
     app_state: Arc<Mutex<AppState>>,
     snooze_length: i64,
+    is_multi_repo: bool,
+    snapshots: bool,
 ) {
     let mut quit_command = command_prompt("Q".to_string(), "quit".to_string(), Color::LightRed);
     let snooze_duration = &Duration::seconds(snooze_length);
@@ -170,6 +229,31 @@ fn draw_footer(
     commands.append(quit_command.as_mut());
     commands.append(spacer.as_mut());
     commands.append(snooze_command.as_mut());
+    let mut commit_command =
+        command_prompt("C".to_string(), "commit".to_string(), Color::LightGreen);
+    commands.append(vec![Span::styled(" / ", Style::default().fg(Color::White))].as_mut());
+    commands.append(commit_command.as_mut());
+    let mut stage_command = command_prompt("S".to_string(), "stage".to_string(), Color::LightGreen);
+    commands.append(vec![Span::styled(" / ", Style::default().fg(Color::White))].as_mut());
+    commands.append(stage_command.as_mut());
+    if snapshots {
+        let mut snapshots_command = command_prompt(
+            "W".to_string(),
+            "browse snapshots".to_string(),
+            Color::LightGreen,
+        );
+        commands.append(vec![Span::styled(" / ", Style::default().fg(Color::White))].as_mut());
+        commands.append(snapshots_command.as_mut());
+    }
+    if is_multi_repo {
+        let mut next_repo_command = command_prompt(
+            "<tab>".to_string(),
+            "switch repo".to_string(),
+            Color::LightCyan,
+        );
+        commands.append(vec![Span::styled(" / ", Style::default().fg(Color::White))].as_mut());
+        commands.append(next_repo_command.as_mut());
+    }
     let commands = Spans::from(commands.clone());
 
     let footer = Paragraph::new(commands)
@@ -195,13 +279,13 @@ fn get_time_left(
             Some(("less than 1".to_string(), "minute".to_string()))
         };
     }
-    return None;
+    None
 }
 
 fn draw_bar(
     threshold: f64,
     max_value: f64,
-    total: i32,
+    score: f64,
     title: Spans,
     f: &mut Frame<CrosstermBackend<Stdout>>,
     bar_area: Rect,
@@ -213,25 +297,134 @@ fn draw_bar(
                 .bg(Color::Indexed(237))
                 .add_modifier(Modifier::ITALIC),
         )
-        .value_and_max_value(total as f64, max_value)
+        .value_and_max_value(score, max_value)
         .threshold(threshold)
         .use_unicode(true);
 
     f.render_widget(bar_graph, bar_area);
 }
 
-fn draw_app_title(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
-    let app_title = Paragraph::new("DiffDing - Commit Reminder")
-        .style(
+/// The selected repository's name and root.
+fn draw_app_title(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_state: &GitState) {
+    let root = &git_state.repo_root;
+
+    let app_title = Paragraph::new(Spans::from(vec![
+        Span::styled(
+            repo_name(git_state),
             Style::default()
                 .fg(Color::Cyan)
-                .bg(Color::Black)
                 .add_modifier(Modifier::BOLD),
-        )
+        ),
+        Span::styled(
+            format!("  {}", root.display()),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ]))
+    .style(Style::default().bg(Color::Black))
+    .block(Block::default().borders(Borders::NONE))
+    .wrap(Wrap { trim: true });
+
+    f.render_widget(app_title, area);
+}
+
+/// Totals across all repositories.
+fn draw_aggregate(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_states: &[GitState]) {
+    let insertions: i32 = git_states.iter().map(|s| s.git_changes.insertions).sum();
+    let deletions: i32 = git_states.iter().map(|s| s.git_changes.deletions).sum();
+    let above_threshold = git_states
+        .iter()
+        .filter(|git_state| git_state.is_above_threshold())
+        .count();
+    let above_color = if above_threshold > 0 {
+        Color::LightRed
+    } else {
+        Color::LightGreen
+    };
+
+    let aggregate = Paragraph::new(Spans::from(vec![
+        Span::styled(
+            format!("All {} repos ", git_states.len()),
+            Style::default().fg(Color::White),
+        ),
+        Span::styled(
+            format!("+{insertions}"),
+            Style::default().fg(Color::LightGreen),
+        ),
+        Span::styled("/", Style::default().fg(Color::White)),
+        Span::styled(
+            format!("-{deletions}"),
+            Style::default().fg(Color::LightRed),
+        ),
+        Span::styled(
+            format!("  {above_threshold} over threshold"),
+            Style::default().fg(above_color),
+        ),
+    ]))
+    .style(Style::default().bg(Color::Black))
+    .block(Block::default().borders(Borders::NONE));
+
+    f.render_widget(aggregate, area);
+}
+
+fn repo_name(git_state: &GitState) -> String {
+    let root = &git_state.repo_root;
+    root.file_name()
+        .map_or_else(|| root.to_string_lossy(), |name| name.to_string_lossy())
+        .to_string()
+}
+
+/// Marks the repository whose details are shown.
+fn repo_label(git_state: &GitState, is_selected: bool) -> String {
+    let marker = if is_selected { ">" } else { " " };
+    format!("{marker} {}", repo_name(git_state))
+}
+
+/// Explains why there's nothing to count, in place of the data display.
+fn draw_repo_status(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_state: &GitState) {
+    let waiting = "Waiting for the repository to become valid...";
+    let (heading, detail, footnote) = match git_state.repo_status {
+        RepoStatus::Ready => return,
+        RepoStatus::NotARepository => (
+            "Not a git repository",
+            "Run diffding inside a repository, pass `--repo <path>`, or run `git init`.",
+            waiting,
+        ),
+        RepoStatus::UnbornBranch => (
+            "No commits yet",
+            "Counting starts after the first commit.",
+            waiting,
+        ),
+        RepoStatus::Bare => (
+            "Bare repository",
+            "There's no working tree to count changes in.",
+            waiting,
+        ),
+        RepoStatus::Failed => (
+            "Couldn't count the changes",
+            git_state.update_error.as_deref().unwrap_or_default(),
+            "Trying again at the next update...",
+        ),
+        RepoStatus::Waiting => ("Counting...", "", ""),
+    };
+
+    let text = vec![
+        Spans::from(Span::styled(
+            heading,
+            Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(""),
+        Spans::from(Span::styled(detail, Style::default().fg(Color::White))),
+        Spans::from(Span::styled(footnote, Style::default().fg(Color::DarkGray))),
+    ];
+
+    let paragraph = Paragraph::new(text)
         .block(Block::default().borders(Borders::NONE))
+        .style(Style::default().bg(Color::Black))
         .wrap(Wrap { trim: true });
 
-    f.render_widget(app_title, area);
+    f.render_widget(paragraph, area);
 }
 
 fn get_data_display(area: Rect, is_wide: bool) -> Vec<Rect> {
@@ -252,7 +445,7 @@ fn get_data_display(area: Rect, is_wide: bool) -> Vec<Rect> {
         [
             Constraint::Length(5),
             Constraint::Length(2),
-            Constraint::Length(5),
+            Constraint::Length(16),
         ]
         .as_ref()
     };
@@ -283,10 +476,87 @@ fn debug_info(f: &mut Frame<CrosstermBackend<Stdout>>, is_wide: bool) {
     f.render_widget(paragraph, Rect::new(0, 0, len + 5, 4));
 }
 
+/// How long ago the selected repository's last commit was, how long it
+/// has been dirty, and any reminders due.
+fn draw_status_line(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_state: &GitState) {
+    if let Some(operation) = git_state.operation {
+        draw_operation_banner(f, area, operation);
+        return;
+    }
+
+    let now = Local::now();
+    let mut spans = Vec::new();
+
+    if let Some(head_commit_time) = git_state.head_commit_time {
+        spans.push(Span::styled(
+            format!("last commit {} ago", format_age(head_commit_time, now)),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    if let Some(dirty_since) = git_state.dirty_since {
+        spans.push(Span::styled(
+            format!(", dirty for {}", format_age(dirty_since, now)),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    let too_large = git_state.git_changes.too_large;
+    if too_large.files > 0 {
+        spans.push(Span::styled(
+            format!(
+                "  Too large to count: {} untracked ({})",
+                too_large.files,
+                format_bytes(too_large.bytes)
+            ),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    if !git_state.reminders.is_empty() {
+        let messages: Vec<String> = git_state
+            .reminders
+            .iter()
+            .map(|reminder| reminder.message(now))
+            .collect();
+        spans.push(Span::styled(
+            format!("  Reminder: {}", messages.join(" / ")),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+
+    let status_line = Paragraph::new(Spans::from(spans))
+        .style(Style::default().bg(Color::Black))
+        .block(Block::default().borders(Borders::NONE));
+
+    f.render_widget(status_line, area);
+}
+
+/// Names the operation in progress and says the bell is paused.
+fn draw_operation_banner(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    operation: Operation,
+) {
+    let banner = Paragraph::new(Span::styled(
+        format!(
+            " {} in progress: the bell is paused until it's finished ",
+            operation.label().to_uppercase()
+        ),
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightMagenta)
+            .add_modifier(Modifier::BOLD),
+    ))
+    .style(Style::default().bg(Color::Black))
+    .block(Block::default().borders(Borders::NONE));
+
+    f.render_widget(banner, area);
+}
+
 fn big_text(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_state: &GitState) {
-    let message = match git_state.is_above_threshold() {
-        true => "COMMIT!",
-        false => "OK!",
+    let message = match (git_state.is_above_threshold(), git_state.reminders.first()) {
+        _ if git_state.operation.is_some() => "PAUSED",
+        (true, _) => "COMMIT!",
+        (false, Some(reminder)) => reminder.headline(),
+        (false, None) => "OK!",
     };
 
     let output = cfonts::render(cfonts::Options {
@@ -304,9 +574,13 @@ fn big_text(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_state: &Git
 
     let text = Text::from(output.text);
 
-    let fg = match git_state.is_above_threshold() {
-        true => Color::LightRed,
-        false => Color::LightGreen,
+    let fg = match (
+        git_state.is_above_threshold(),
+        git_state.reminders.is_empty(),
+    ) {
+        (true, _) => Color::LightRed,
+        (false, false) => Color::LightYellow,
+        (false, true) => Color::LightGreen,
     };
 
     let paragraph = Paragraph::new(text)
@@ -317,13 +591,31 @@ fn big_text(f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, git_state: &Git
     f.render_widget(paragraph, area);
 }
 
-fn git_summary<'ui>(git_state: GitState) -> Spans<'ui> {
+fn git_summary<'ui>(git_state: GitState, label: Option<String>) -> Spans<'ui> {
     let insertions: u16 = git_state.git_changes.insertions as u16;
     let deletions: u16 = git_state.git_changes.deletions as u16;
 
-    let title: Spans<'ui> = Spans::from(vec![
+    let mut spans = Vec::new();
+    if let Some(label) = label {
+        spans.push(Span::styled(
+            format!("{label} "),
+            Style::default()
+                .fg(Color::Cyan)
+                .bg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::styled(
+        format!("{} ", branch_summary(&git_state.branch, git_state.vcs)),
+        Style::default().fg(Color::LightMagenta).bg(Color::Black),
+    ));
+    spans.extend([
         Span::styled(
-            format!("{} ", git_state.current_commit_short),
+            format!(
+                "{} {} ",
+                git_state.vcs.revision_label(),
+                git_state.current_commit_short
+            ),
             Style::default().fg(Color::White).bg(Color::Black),
         ),
         Span::styled(
@@ -336,7 +628,26 @@ fn git_summary<'ui>(git_state: GitState) -> Spans<'ui> {
             Style::default().fg(Color::LightRed).bg(Color::Black),
         ),
     ]);
-    title
+    Spans::from(spans)
+}
+
+/// Like `main ↑2 ↓1`, with the arrows only when the branch has diverged
+/// from its upstream.
+fn branch_summary(branch: &BranchState, vcs: Vcs) -> String {
+    let Some(name) = &branch.name else {
+        return format!("({})", vcs.no_branch_label());
+    };
+
+    let mut summary = name.clone();
+    if let Some(upstream) = &branch.upstream {
+        if upstream.ahead > 0 {
+            summary.push_str(&format!(" ↑{}", upstream.ahead));
+        }
+        if upstream.behind > 0 {
+            summary.push_str(&format!(" ↓{}", upstream.behind));
+        }
+    }
+    summary
 }
 
 fn command_prompt<'a>(key_name: String, action: String, color: Color) -> Vec<Span<'a>> {
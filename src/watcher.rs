@@ -0,0 +1,327 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::operations;
+
+/// How `git_loop` finds out that it should recount.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RefreshMode {
+    /// Filesystem events, plus a slow safety interval
+    Watching,
+    /// Fixed interval
+    #[default]
+    Polling,
+    /// Fixed interval, because the system ran out of file watches
+    WatchLimit,
+}
+
+/// The directories in a git directory that only hold what doesn't matter
+/// for the counts, left unwatched in submodules' git directories.
+const UNWATCHED_GIT_DIRS: &[&str] = &["objects", "logs", "hooks", "info", "lfs"];
+
+/// A running watch on the worktree and the git directory. The watcher
+/// stops when it's dropped.
+pub struct Watch {
+    watcher: RecommendedWatcher,
+    /// A message whenever something that could change the counts is touched
+    pub events: UnboundedReceiver<()>,
+    /// Directories created since they were last watched
+    new_dirs: UnboundedReceiver<PathBuf>,
+    git_dir: PathBuf,
+    ignore_rules: IgnoreRules,
+}
+
+impl Watch {
+    /// Watches the directories created in the worktree since the last call,
+    /// and everything in them.
+    pub fn watch_new_dirs(&mut self) -> notify::Result<()> {
+        while let Ok(dir) = self.new_dirs.try_recv() {
+            let dirs = directories(&dir, &|path| {
+                is_skipped_dir(path, &self.git_dir, &self.ignore_rules)
+            });
+            watch_each(&mut self.watcher, &dirs)?;
+        }
+        Ok(())
+    }
+}
+
+/// Watches the worktree and the git directory, sending a message whenever
+/// something that could change the counts is touched.
+///
+/// Each directory gets a watch of its own, skipping ignored ones like
+/// `target/` or `node_modules/`, so big build trees don't use up the
+/// system's file watches.
+pub fn watch(workdir: &Path, git_dir: &Path) -> notify::Result<Watch> {
+    let (tx, events) = unbounded_channel();
+    let (tx_dirs, new_dirs) = unbounded_channel();
+    let callback_git_dir = git_dir.to_path_buf();
+    let callback_ignore_rules = IgnoreRules::open(workdir);
+
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        if let Ok(event) = result {
+            let is_ignored = |path: &Path| callback_ignore_rules.is_ignored(path);
+            if !is_relevant(&event, &callback_git_dir, is_ignored) {
+                return;
+            }
+            // Directories made or moved into the worktree need watches too
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                for path in &event.paths {
+                    if path.is_dir()
+                        && !is_skipped_dir(path, &callback_git_dir, &callback_ignore_rules)
+                    {
+                        let _ = tx_dirs.send(path.clone());
+                    }
+                }
+            }
+            let _ = tx.send(());
+        }
+    })?;
+
+    let ignore_rules = IgnoreRules::open(workdir);
+    let worktree_dirs = directories(workdir, &|path| {
+        is_skipped_dir(path, git_dir, &ignore_rules)
+    });
+    watch_each(&mut watcher, &worktree_dirs)?;
+
+    // The index, HEAD and the files marking a merge or rebase sit at the
+    // top, refs can nest, and submodules keep their git directories under
+    // `modules/`
+    watcher.watch(git_dir, RecursiveMode::NonRecursive)?;
+    for refs in [git_dir.join("refs"), git_dir.join("repo/op_heads")] {
+        if refs.is_dir() {
+            watcher.watch(&refs, RecursiveMode::Recursive)?;
+        }
+    }
+    let modules = git_dir.join("modules");
+    if modules.is_dir() {
+        let module_dirs = directories(&modules, &|path| {
+            path.file_name()
+                .is_some_and(|name| UNWATCHED_GIT_DIRS.iter().any(|dir| name == *dir))
+        });
+        watch_each(&mut watcher, &module_dirs)?;
+    }
+
+    Ok(Watch {
+        watcher,
+        events,
+        new_dirs,
+        git_dir: git_dir.to_path_buf(),
+        ignore_rules,
+    })
+}
+
+/// Whether `error` means the system ran out of file watches, as inotify
+/// does past `fs.inotify.max_user_watches`.
+pub fn is_watch_limit(error: &notify::Error) -> bool {
+    matches!(error.kind, notify::ErrorKind::MaxFilesWatch)
+}
+
+/// Adds a watch for each of `dirs`. Running out of watches is an error;
+/// a directory that's gone, or can't be read, is skipped.
+fn watch_each(watcher: &mut RecommendedWatcher, dirs: &[PathBuf]) -> notify::Result<()> {
+    for dir in dirs {
+        match watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Err(error) if is_watch_limit(&error) => return Err(error),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `dir` and the directories under it, leaving out those `skip` matches
+/// along with everything in them. Symlinks aren't followed.
+fn directories(dir: &Path, skip: &dyn Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut dirs = vec![dir.to_path_buf()];
+    let mut next = 0;
+    while let Some(dir) = dirs.get(next).cloned() {
+        next += 1;
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        dirs.extend(
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                .map(|entry| entry.path())
+                .filter(|path| !skip(path)),
+        );
+    }
+    dirs
+}
+
+/// The git directory is watched on its own, and other `.git` directories
+/// and ignored ones don't count.
+fn is_skipped_dir(path: &Path, git_dir: &Path, ignore_rules: &IgnoreRules) -> bool {
+    path.starts_with(git_dir)
+        || path
+            .components()
+            .any(|component| component.as_os_str() == ".git")
+        || ignore_rules.is_ignored(path)
+}
+
+/// The worktree's ignore rules, read by libgit2 so they're the same for
+/// every backend. Where git can't open the repository, as in a plain
+/// Mercurial one, nothing is ignored.
+struct IgnoreRules {
+    workdir: PathBuf,
+    repo: Option<Repository>,
+}
+
+impl IgnoreRules {
+    fn open(workdir: &Path) -> Self {
+        Self {
+            workdir: workdir.to_path_buf(),
+            repo: Repository::open(workdir).ok(),
+        }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        let (Some(repo), Ok(relative)) = (&self.repo, path.strip_prefix(&self.workdir)) else {
+            return false;
+        };
+        repo.is_path_ignored(relative).unwrap_or(false)
+    }
+}
+
+fn is_relevant(event: &Event, git_dir: &Path, is_ignored: impl Fn(&Path) -> bool) -> bool {
+    match event.kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => {}
+        EventKind::Access(_) | EventKind::Other => return false,
+        _ => {}
+    }
+
+    event
+        .paths
+        .iter()
+        .any(|path| is_relevant_path(path, git_dir, &is_ignored))
+}
+
+/// Inside the git directory only the index, HEAD, refs and the files that
+/// mark a merge, rebase and so on matter; object
+/// writes, lock files and logs would otherwise trigger a recount for every
+/// git command. Submodules keep their git directories under `modules/`.
+///
+/// Mercurial's `dirstate` plays the part of the index, and a new jj
+/// operation shows up under `repo/op_heads`.
+///
+/// In the worktree, ignored paths such as build output don't count, and
+/// neither do other `.git` directories, like the one a colocated jj
+/// repository keeps next to `.jj`.
+fn is_relevant_path(path: &Path, git_dir: &Path, is_ignored: impl Fn(&Path) -> bool) -> bool {
+    match path.strip_prefix(git_dir) {
+        Ok(relative) if relative.starts_with("modules") => relative
+            .file_name()
+            .is_some_and(|name| name == "index" || name == "HEAD"),
+        Ok(relative) => {
+            relative == Path::new("index")
+                || relative == Path::new("dirstate")
+                || relative.starts_with("repo/op_heads")
+                || operations::is_operation_path(relative)
+                || relative == Path::new("HEAD")
+                || (relative.starts_with("refs")
+                    && relative
+                        .extension()
+                        .is_none_or(|extension| extension != "lock"))
+        }
+        Err(_) => {
+            !path
+                .components()
+                .any(|component| component.as_os_str() == ".git")
+                && !is_ignored(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_directory_only_counts_the_index_head_and_refs() {
+        let git_dir = Path::new("/repo/.git");
+        let nothing_ignored = |_: &Path| false;
+
+        assert!(is_relevant_path(
+            Path::new("/repo/.git/index"),
+            git_dir,
+            nothing_ignored
+        ));
+        assert!(is_relevant_path(
+            Path::new("/repo/.git/refs/heads/main"),
+            git_dir,
+            nothing_ignored
+        ));
+        assert!(!is_relevant_path(
+            Path::new("/repo/.git/objects/ab/cdef"),
+            git_dir,
+            nothing_ignored
+        ));
+    }
+
+    #[test]
+    fn ignored_paths_and_other_git_directories_dont_count() {
+        let git_dir = Path::new("/repo/.jj");
+        let is_ignored = |path: &Path| path.starts_with("/repo/target");
+
+        assert!(is_relevant_path(
+            Path::new("/repo/src/main.rs"),
+            git_dir,
+            is_ignored
+        ));
+        assert!(!is_relevant_path(
+            Path::new("/repo/target/debug/diffding"),
+            git_dir,
+            is_ignored
+        ));
+        assert!(!is_relevant_path(
+            Path::new("/repo/.git/objects/ab/cdef"),
+            git_dir,
+            is_ignored
+        ));
+    }
+
+    #[test]
+    fn ignore_rules_come_from_gitignore() {
+        let dir = std::env::temp_dir().join(format!("diffding-watch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Repository::init(&dir).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+
+        let ignore_rules = IgnoreRules::open(&dir);
+
+        assert!(ignore_rules.is_ignored(&dir.join("target/debug/diffding")));
+        assert!(!ignore_rules.is_ignored(&dir.join("src/main.rs")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ignored_directories_get_no_watches() {
+        let dir = std::env::temp_dir().join(format!("diffding-walk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Repository::init(&dir).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        for sub in ["src/bin", "target/debug/deps", "vendor/lib/.git/objects"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        let git_dir = dir.join(".git");
+        let ignore_rules = IgnoreRules::open(&dir);
+
+        let mut dirs = directories(&dir, &|path| is_skipped_dir(path, &git_dir, &ignore_rules));
+        dirs.sort();
+
+        let expected: Vec<PathBuf> = ["", "src", "src/bin", "vendor", "vendor/lib"]
+            .iter()
+            .map(|sub| dir.join(sub))
+            .collect();
+        assert_eq!(dirs, expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}